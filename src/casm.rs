@@ -1,14 +1,26 @@
-use crate::ctab::Ctab;
+use crate::ctab::{pitch_name, AccompanimentPart, Chord, Ctab, Key};
 use crate::prelude::*;
-use crate::smf::{Chunk, ChunkIter};
+use crate::smf::{write_chunk, Chunk, ChunkIter};
+use crate::style::ParseOptions;
 use crate::Error;
+use core::iter::FusedIterator;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 
+/// The CASM section of a style file.
+///
+/// It holds the style segments (CSEG), which link the style parts to the accompaniment tables
+/// (CTAB) describing how each source channel of the MIDI section is played back.
 #[derive(Clone, Debug)]
-pub struct Casm<'a>(pub(crate) CsegIter<'a>);
+pub struct Casm<'a> {
+    csegs: Vec<Cseg<'a>>,
+    // The content of the CASM chunk, if retained.
+    raw: Option<&'a [u8]>,
+}
 
 impl<'a> Casm<'a> {
     // get the first CASM section from a ChunkIter, additional ones are ignored.
-    pub(crate) fn parse(chunk_iter: ChunkIter<'a>) -> Result<Option<Self>> {
+    pub(crate) fn parse(chunk_iter: ChunkIter<'a>, options: &ParseOptions) -> Result<Option<Self>> {
         let mut casm_iter = chunk_iter.filter(|c| matches!(c, Ok(Chunk::Casm(..))));
         // Take only the first CASM section found if any
         let casm = match casm_iter.next() {
@@ -19,119 +31,575 @@ impl<'a> Casm<'a> {
             None => return Ok(None),
         }?;
 
-        Ok(Some(Casm(CsegIter {
-            inner: ChunkIter::new(casm),
-        })))
+        let mut cseg_iter = CsegIter::new(casm);
+        let csegs =
+            core::iter::from_fn(|| cseg_iter.next_with(options)).collect::<Result<Vec<_>>>()?;
+        let raw = Some(casm).filter(|_| options.retain_raw);
+        Ok(Some(Casm { csegs, raw }))
+    }
+
+    // A section holding the given style segments.
+    pub(crate) fn new(csegs: Vec<Cseg<'a>>) -> Casm<'a> {
+        Casm { csegs, raw: None }
+    }
+
+    /// Encode a complete CASM section holding the given style segments.
+    ///
+    /// Each segment is written as a CSEG chunk, see [`Cseg::write`].
+    /// Fails if one of the tables cannot be encoded, see [`Ctab::write`].
+    pub fn build(csegs: Vec<OwnedCseg>) -> StdResult<Vec<u8>, &'static str> {
+        let mut out = Vec::new();
+        Casm::new(csegs).write(&mut out)?;
+        Ok(out)
+    }
+
+    /// Write this section as a `CASM` chunk.
+    pub fn write<W: Write>(&self, out: &mut W) -> WriteResult<W> {
+        let mut data = Vec::new();
+        for cseg in &self.csegs {
+            cseg.write(&mut data).map_err(W::invalid_input)?;
+        }
+        write_chunk(b"CASM", &data, out)
+    }
+
+    /// The style segments of this section, in file order.
+    pub fn csegs(&self) -> &[Cseg<'a>] {
+        &self.csegs
+    }
+
+    /// The content of the CASM chunk this section was parsed from, if it was parsed with
+    /// [`ParseOptions::retain_raw`].
+    ///
+    /// These are the bytes as read: changes made to the section are not reflected here.
+    pub fn raw(&self) -> Option<&'a [u8]> {
+        self.raw
+    }
+
+    /// Whether this section holds no style segment.
+    pub fn is_empty(&self) -> bool {
+        self.csegs.is_empty()
+    }
+
+    /// The first table, in file order, for which `pred` returns `true`, searching every style
+    /// segment.
+    pub fn find_ctab(&self, pred: impl Fn(&Ctab) -> bool) -> Option<&Ctab<'a>> {
+        self.csegs
+            .iter()
+            .flat_map(|cseg| cseg.ctabs())
+            .find(|ctab| pred(ctab))
+    }
+
+    // Consume the section, yielding every table in file order.
+    pub(crate) fn into_ctabs(self) -> impl Iterator<Item = Ctab<'a>> {
+        self.csegs.into_iter().flat_map(|cseg| cseg.ctab)
+    }
+
+    /// Write an indented outline of this section, for debugging.
+    ///
+    /// Each style segment is listed with its style parts, followed by its tables with their
+    /// channels, and the transposition tables of each one:
+    ///
+    /// ```text
+    /// CASM
+    ///   CSEG 0: Main A, Main B
+    ///     CTAB 0 "E.Bass": Ch11 -> Ch11 (Bass)
+    ///       Table 0: RootTransposition, Melody, high key E♭, notes E1..G9, bass on
+    /// ```
+    pub fn print_tree(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        writeln!(out, "CASM")?;
+        for (index, cseg) in self.csegs.iter().enumerate() {
+            let parts: Vec<&str> = cseg.style_parts.iter().map(|&part| part.into()).collect();
+            writeln!(out, "  CSEG {}: {}", index, parts.join(", "))?;
+            for (index, ctab) in cseg.ctab.iter().enumerate() {
+                write!(
+                    out,
+                    "    CTAB {} \"{}\": Ch{} -> Ch{}",
+                    index,
+                    ctab.name(),
+                    ctab.source_channel_number(),
+                    ctab.dest_channel_number()
+                )?;
+                match ctab.dest_role() {
+                    Some(role) => writeln!(out, " ({:?})", role)?,
+                    None => writeln!(out)?,
+                }
+                for (index, table) in ctab.tables().iter().enumerate() {
+                    let (low, high) = table.note_range();
+                    write!(
+                        out,
+                        "      Table {}: {:?}, {:?}, high key {}, notes {}..{}",
+                        index,
+                        table.ntr(),
+                        table.ntt(),
+                        table.high_key(),
+                        pitch_name(low),
+                        pitch_name(high)
+                    )?;
+                    if table.bass_on() {
+                        write!(out, ", bass on")?;
+                    }
+                    writeln!(out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The key the style was recorded in, or `None` if there are no tables.
+    ///
+    /// This is a heuristic: the source chord of each table is the chord its track was recorded
+    /// against, and most of the tracks of a style are usually recorded in the same key.
+    /// The most common source chord root across all tables is returned, ties going to the key
+    /// found first.
+    pub fn reference_key(&self) -> Option<Key> {
+        let mut counts = [0_usize; 12];
+        let mut order = Vec::new();
+        for ctab in self.csegs.iter().flat_map(|cseg| cseg.ctabs()) {
+            let key = ctab.source_chord();
+            if counts[key as usize] == 0 {
+                order.push(key);
+            }
+            counts[key as usize] += 1;
+        }
+        order
+            .into_iter()
+            .rev()
+            .max_by_key(|&key| counts[key as usize])
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct Cseg<'a> {
+/// Iterate over the style segments, in file order.
+impl<'a, 'b> IntoIterator for &'b Casm<'a> {
+    type Item = &'b Cseg<'a>;
+    type IntoIter = core::slice::Iter<'b, Cseg<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.csegs.iter()
+    }
+}
+
+/// Consume the section, yielding its style segments in file order.
+impl<'a> IntoIterator for Casm<'a> {
+    type Item = Cseg<'a>;
+    type IntoIter = std::vec::IntoIter<Cseg<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.csegs.into_iter()
+    }
+}
+
+/// A style segment (CSEG), associating a set of style parts with their accompaniment tables.
+#[derive(Clone, Debug)]
+pub struct Cseg<'a> {
+    sdec: Cow<'a, [u8]>,
     style_parts: Vec<StylePart>,
     ctab: Vec<Ctab<'a>>,
 }
 
+/// A style segment that does not borrow from the bytes it was parsed from.
+pub type OwnedCseg = Cseg<'static>;
+
 impl<'a> Cseg<'a> {
-    fn read(chunk: Chunk) -> Result<Cseg> {
-        let value = match chunk {
-            Chunk::Cseg(v) => v,
+    /// Create a segment applying the given tables to the given style parts.
+    ///
+    /// The SDEC chunk lists the style parts separated by `", "`, as done by the instruments.
+    pub fn new(style_parts: Vec<StylePart>, ctabs: Vec<Ctab<'a>>) -> Cseg<'a> {
+        Cseg {
+            sdec: Cow::Owned(encode_sdec(&style_parts)),
+            style_parts,
+            ctab: ctabs,
+        }
+    }
+
+    /// The style parts this segment applies to, as listed in its SDEC chunk.
+    pub fn style_parts(&self) -> &[StylePart] {
+        &self.style_parts
+    }
+
+    /// The style part labels of the SDEC chunk, as written in the file.
+    ///
+    /// Unlike [`Cseg::style_parts`], labels that are not known style parts are kept.
+    /// Labels are trimmed of surrounding whitespace, and labels that are not valid UTF-8 are
+    /// skipped.
+    pub fn sdec_raw(&self) -> Vec<&str> {
+        self.sdec
+            .split(|b| *b == b',')
+            .filter_map(|label| std::str::from_utf8(label).ok())
+            .map(str::trim)
+            .collect()
+    }
+
+    /// The accompaniment tables of this segment, in file order.
+    pub fn ctabs(&self) -> &[Ctab<'a>] {
+        &self.ctab
+    }
+
+    /// The accompaniment parts populated by this segment, along with the table playing each one.
+    ///
+    /// Tables whose destination is not an accompaniment channel are skipped.
+    /// If several tables share the same destination, the first one is kept.
+    pub fn roles(&self) -> BTreeMap<AccompanimentPart, &Ctab<'a>> {
+        let mut roles = BTreeMap::new();
+        for ctab in &self.ctab {
+            if let Some(role) = ctab.dest_role() {
+                roles.entry(role).or_insert(ctab);
+            }
+        }
+        roles
+    }
+
+    /// Copy any data borrowed from the parsed bytes, so that the segment can outlive them.
+    pub fn into_owned(self) -> OwnedCseg {
+        Cseg {
+            sdec: Cow::Owned(self.sdec.into_owned()),
+            style_parts: self.style_parts,
+            ctab: self.ctab.into_iter().map(Ctab::into_owned).collect(),
+        }
+    }
+
+    /// Write this segment as a `CSEG` chunk.
+    ///
+    /// The SDEC chunk is written as it was read, keeping its separators and the labels that are
+    /// not known style parts, or as listed by [`Cseg::new`]. The tables follow, see
+    /// [`Ctab::write`].
+    pub fn write<W: Write>(&self, out: &mut W) -> WriteResult<W> {
+        let mut data = Vec::new();
+        write_chunk(b"Sdec", &self.sdec, &mut data).map_err(W::invalid_input)?;
+        for ctab in &self.ctab {
+            ctab.write(&mut data).map_err(W::invalid_input)?;
+        }
+        write_chunk(b"CSEG", &data, out)
+    }
+
+    /// Check the consistency of the SDEC and CTAB chunks of this segment.
+    ///
+    /// This only looks at the segment itself, the returned warnings are empty for a consistent
+    /// segment.
+    pub fn validate(&self) -> Vec<CsegWarning> {
+        let mut warnings = Vec::new();
+        if !self.style_parts.is_empty() && self.ctab.is_empty() {
+            warnings.push(CsegWarning::NoCtab);
+        }
+        if self.style_parts.is_empty() && !self.ctab.is_empty() {
+            warnings.push(CsegWarning::NoStylePart);
+        }
+        for (index, ctab) in self.ctab.iter().enumerate() {
+            // Several source channels are commonly merged into the same destination, so only a
+            // repeated source and destination pair is ambiguous.
+            let (source, dest) = (ctab.source(), ctab.dest());
+            if let Some(first) = self.ctab[..index]
+                .iter()
+                .position(|c| c.source() == source && c.dest() == dest)
+            {
+                warnings.push(CsegWarning::DuplicateDest {
+                    dest,
+                    first,
+                    second: index,
+                });
+            }
+            let chord = ctab.source_chord_type();
+            if !chord.is_chord_type() {
+                warnings.push(CsegWarning::SpecialSourceChordType { index, chord });
+            }
+            if !ctab.trailer_present() {
+                warnings.push(CsegWarning::MissingTrailer { index });
+            }
+        }
+        warnings
+    }
+
+    fn read(chunk: Chunk<'a>, options: &ParseOptions) -> Result<Cseg<'a>> {
+        // The content of a CSEG is made of chunks
+        let chunk_iter = match chunk {
+            Chunk::Cseg(..) => chunk.sub_chunks().unwrap_or_default(),
             _ => bail!(err_invalid!("not a CSEG chunk")),
         };
-
-        // Following sections are chunks
-        let chunk_iter = ChunkIter::new(value);
+        let mut sdec: &'a [u8] = &[];
         let mut style_parts: Vec<StylePart> = vec![];
         let mut ctab: Vec<Ctab> = vec![];
         for chunk in chunk_iter {
             match chunk {
                 Ok(Chunk::Sdec(data)) => {
-                    // Style parts are separated by ',' (0x2C)
+                    sdec = data;
+                    // Style parts are separated by ',' (0x2C), usually followed by a space
                     let parts = &mut data.split(|b| *b == 0x2C_u8);
                     for maybe_parts in parts {
-                        match StylePart::try_from(maybe_parts) {
+                        match StylePart::try_from(trim_label(maybe_parts)) {
                             Ok(part) => style_parts.push(part),
-                            Err(_) => Err(err_malformed!("could not read style part value"))?,
+                            Err(_) if options.strict() => {
+                                bail!(err_malformed!("could not read style part value"))
+                            }
+                            // Unknown labels are still available from `sdec_raw`
+                            Err(err) => options.skip(err),
                         };
                     }
                 }
-                Ok(c) if matches!(c, Chunk::Ctab1(..)) => {
-                    let maybe_ctab = Ctab::read(c)?;
-                    ctab.push(maybe_ctab);
-                }
-                Ok(c) if matches!(c, Chunk::Ctab2(..)) => {
-                    let maybe_ctab = Ctab::read(c)?;
+                Ok(c) if matches!(c, Chunk::Ctab1(..) | Chunk::Ctab2(..)) => {
+                    let maybe_ctab = Ctab::read_with(c, options)?;
+                    if !maybe_ctab.trailer_present() {
+                        options.skip(err_malformed!("missing special bytes at the end of CTAB"));
+                    }
                     ctab.push(maybe_ctab);
                 }
                 // TODO: change when CNTT is implemented
                 Ok(Chunk::Cntt(_)) => {}
-                Ok(_) => Err(err_invalid!(
+                Ok(_) if options.strict() => Err(err_invalid!(
                     "found a chunk not belonging in a CASM section"
                 ))?,
+                // Stray chunks are dropped, the rest of the segment is still read
+                Ok(_) => options.skip(err_invalid!(
+                    "found a chunk not belonging in a CASM section"
+                )),
                 Err(_) => Err(err_invalid!("could not read chunk"))?,
             }
         }
-        Ok(Cseg { style_parts, ctab })
+        Ok(Cseg {
+            sdec: Cow::Borrowed(sdec),
+            style_parts,
+            ctab,
+        })
     }
 }
 
+// The content of a SDEC chunk, listing the given style parts.
+fn encode_sdec(style_parts: &[StylePart]) -> Vec<u8> {
+    let labels: Vec<&[u8]> = style_parts.iter().map(|&part| part.into()).collect();
+    labels.join(&b", "[..])
+}
+
+/// An inconsistency within a style segment, as reported by [`Cseg::validate`].
+///
+/// Tables are referred to by their index in [`Cseg::ctabs`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsegWarning {
+    /// The SDEC chunk lists style parts, but there is no table to play them.
+    NoCtab,
+    /// There are tables, but the SDEC chunk lists no style part to use them in.
+    NoStylePart,
+    /// Two tables route the same source channel to the same destination channel.
+    ///
+    /// Tables with different source channels may share a destination, their notes are merged.
+    DuplicateDest {
+        /// The shared destination channel.
+        dest: u4,
+        /// Index of the first table with this destination.
+        first: usize,
+        /// Index of the duplicate table.
+        second: usize,
+    },
+    /// The source chord type of a table is not an actual chord, such as
+    /// [`Chord::SpecialAutostart`].
+    SpecialSourceChordType {
+        /// Index of the table.
+        index: usize,
+        /// The offending source chord type.
+        chord: Chord,
+    },
+    /// A table was cut short before its special bytes, and may not be written back as it was
+    /// read. See [`Ctab::trailer_present`].
+    MissingTrailer {
+        /// Index of the table.
+        index: usize,
+    },
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct CsegIter<'a> {
     inner: ChunkIter<'a>,
 }
 
-impl<'a> Iterator for CsegIter<'a> {
-    type Item = Result<Cseg<'a>>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let chunk = self.inner.next()?;
-        match chunk {
-            Ok(c) if matches!(c, Chunk::Cseg(..)) => match Cseg::read(c) {
-                Ok(cseg) => Some(Ok(cseg)),
+impl<'a> CsegIter<'a> {
+    pub(crate) fn new(raw: &'a [u8]) -> CsegIter<'a> {
+        CsegIter {
+            inner: ChunkIter::new(raw),
+        }
+    }
+}
+
+impl<'a> CsegIter<'a> {
+    /// Same as `next`, but reporting the errors that are skipped outside of strict mode.
+    pub(crate) fn next_with(&mut self, options: &ParseOptions) -> Option<Result<Cseg<'a>>> {
+        let item = loop {
+            let chunk = self.inner.next()?;
+            break match chunk {
+                Ok(c) if matches!(c, Chunk::Cseg(..)) => match Cseg::read(c, options) {
+                    Ok(item) => Some(Ok(item)),
+                    Err(err) => {
+                        let err = Err(err).context(err_invalid!("invalid CSEG"));
+                        if options.strict() {
+                            Some(err)
+                        } else {
+                            // Drop this one and carry on with the next chunk
+                            options.skip_result(err);
+                            continue;
+                        }
+                    }
+                },
+                Ok(_) if options.strict() => Some(Err(err_invalid!(
+                    "found a chunk not belonging in a CASM section"
+                )
+                .into())),
+                // Stray chunks are dropped, the following ones are still read
+                Ok(_) => {
+                    options.skip(err_invalid!(
+                        "found a chunk not belonging in a CASM section"
+                    ));
+                    continue;
+                }
                 Err(err) => {
-                    if cfg!(feature = "strict") {
-                        Some(Err(err).context(err_invalid!("invalid CSEG")))
+                    // The chunk boundaries are lost, so nothing can be read after it
+                    let err = Err(err).context(err_malformed!("malformed CSEG"));
+                    if options.strict() {
+                        Some(err)
                     } else {
-                        None
+                        options.skip_result(err)
                     }
                 }
-            },
-            // Wrong chunk type
-            Ok(_) => None,
-            Err(err) => {
-                if cfg!(feature = "strict") {
-                    Some(Err(err).context(err_malformed!("malformed CSEG")))
-                } else {
-                    None
-                }
-            }
+            };
+        };
+        if !matches!(item, Some(Ok(_))) {
+            self.inner = ChunkIter::new(&[]);
         }
+        item
+    }
+}
+
+/// Once an error is yielded or the iteration stops, the iterator is exhausted.
+impl<'a> Iterator for CsegIter<'a> {
+    type Item = Result<Cseg<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with(&ParseOptions::default())
     }
 }
 
+impl FusedIterator for CsegIter<'_> {}
+
 /// Known style sections
 ///
 /// [StylePart::IntroD] and [StylePart::EndingD] are only available for the PSR-2000
 /// [StylePart::FillInBA] corresponds to the "Break" section
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub(crate) enum StylePart {
+///
+/// Style parts are ordered as declared: intros, then mains, then fill ins, then endings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StylePart {
+    /// "Intro A"
     IntroA,
+    /// "Intro B"
     IntroB,
+    /// "Intro C"
     IntroC,
+    /// "Intro D", PSR-2000 only
     IntroD,
+    /// "Main A"
     MainA,
+    /// "Main B"
     MainB,
+    /// "Main C"
     MainC,
+    /// "Main D"
     MainD,
+    /// "Fill In AA"
     FillInAA,
+    /// "Fill In BB"
     FillInBB,
+    /// "Fill In CC"
     FillInCC,
+    /// "Fill In DD"
     FillInDD,
+    /// "Fill In BA", also known as "Break"
+    ///
+    /// Both names are parsed, but it is always written as "Fill In BA".
     FillInBA,
+    /// "Ending A"
     EndingA,
+    /// "Ending B"
     EndingB,
+    /// "Ending C"
     EndingC,
+    /// "Ending D", PSR-2000 only
     EndingD,
 }
 
+impl StylePart {
+    /// All style parts, in declaration order.
+    pub(crate) const ALL: [StylePart; 17] = [
+        StylePart::IntroA,
+        StylePart::IntroB,
+        StylePart::IntroC,
+        StylePart::IntroD,
+        StylePart::MainA,
+        StylePart::MainB,
+        StylePart::MainC,
+        StylePart::MainD,
+        StylePart::FillInAA,
+        StylePart::FillInBB,
+        StylePart::FillInCC,
+        StylePart::FillInDD,
+        StylePart::FillInBA,
+        StylePart::EndingA,
+        StylePart::EndingB,
+        StylePart::EndingC,
+        StylePart::EndingD,
+    ];
+
+    /// Compact index of this style part, from `0` to `16` in declaration order.
+    #[inline]
+    pub fn index(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Get the style part from its compact index, as returned by [`StylePart::index`].
+    #[inline]
+    pub fn from_index(index: u8) -> Option<StylePart> {
+        StylePart::ALL.get(index as usize).copied()
+    }
+
+    /// The kind of section this style part belongs to.
+    pub fn category(&self) -> StyleCategory {
+        match self {
+            StylePart::IntroA | StylePart::IntroB | StylePart::IntroC | StylePart::IntroD => {
+                StyleCategory::Intro
+            }
+            StylePart::MainA | StylePart::MainB | StylePart::MainC | StylePart::MainD => {
+                StyleCategory::Main
+            }
+            StylePart::FillInAA
+            | StylePart::FillInBB
+            | StylePart::FillInCC
+            | StylePart::FillInDD
+            | StylePart::FillInBA => StyleCategory::FillIn,
+            StylePart::EndingA | StylePart::EndingB | StylePart::EndingC | StylePart::EndingD => {
+                StyleCategory::Ending
+            }
+        }
+    }
+}
+
+/// Kind of section of a [`StylePart`], as returned by [`StylePart::category`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StyleCategory {
+    /// Intro A to D
+    Intro,
+    /// Main A to D
+    Main,
+    /// Fill In AA to DD, and the "Break" ([`StylePart::FillInBA`])
+    FillIn,
+    /// Ending A to D
+    Ending,
+}
+
+impl StyleCategory {
+    /// The style parts of this category, in declaration order.
+    pub fn parts(&self) -> &'static [StylePart] {
+        match self {
+            StyleCategory::Intro => &StylePart::ALL[0..4],
+            StyleCategory::Main => &StylePart::ALL[4..8],
+            StyleCategory::FillIn => &StylePart::ALL[8..13],
+            StyleCategory::Ending => &StylePart::ALL[13..17],
+        }
+    }
+}
+
 impl TryFrom<&str> for StylePart {
     type Error = Error;
 
@@ -150,7 +618,7 @@ impl TryFrom<&str> for StylePart {
             "Fill In BB" => Ok(StylePart::FillInBB),
             "Fill In CC" => Ok(StylePart::FillInCC),
             "Fill In DD" => Ok(StylePart::FillInDD),
-            "Fill In BA" => Ok(StylePart::FillInBA),
+            "Fill In BA" | "Break" => Ok(StylePart::FillInBA),
             "Ending A" => Ok(StylePart::EndingA),
             "Ending B" => Ok(StylePart::EndingB),
             "Ending C" => Ok(StylePart::EndingC),
@@ -184,6 +652,23 @@ impl<'a> From<StylePart> for &'a str {
     }
 }
 
+// Strip the ASCII whitespace around a style part label.
+fn trim_label(mut label: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = label {
+        if !first.is_ascii_whitespace() {
+            break;
+        }
+        label = rest;
+    }
+    while let [rest @ .., last] = label {
+        if !last.is_ascii_whitespace() {
+            break;
+        }
+        label = rest;
+    }
+    label
+}
+
 impl<'a> TryFrom<&'a [u8]> for StylePart {
     type Error = Error;
 
@@ -202,7 +687,7 @@ impl<'a> TryFrom<&'a [u8]> for StylePart {
             b"Fill In BB" => Ok(StylePart::FillInBB),
             b"Fill In CC" => Ok(StylePart::FillInCC),
             b"Fill In DD" => Ok(StylePart::FillInDD),
-            b"Fill In BA" => Ok(StylePart::FillInBA),
+            b"Fill In BA" | b"Break" => Ok(StylePart::FillInBA),
             b"Ending A" => Ok(StylePart::EndingA),
             b"Ending B" => Ok(StylePart::EndingB),
             b"Ending C" => Ok(StylePart::EndingC),
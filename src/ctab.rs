@@ -1,24 +1,97 @@
+use crate::error::unsupported;
+use crate::mdb::{read_string, Encoding};
 use crate::prelude::*;
-use crate::smf::Chunk;
+use crate::smf::{write_chunk, Chunk};
+use crate::style::ParseOptions;
 use crate::Error;
-use core::convert::TryInto;
+use core::hash::{Hash, Hasher};
+use core::ops::RangeInclusive;
+use core::str::FromStr;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
-// Size of the various sections found in a CTAB chunk
-const COMMON_SIZE: usize = 20;
-const TABLE_SIZE: usize = 6;
-const CTAB1_SIZE: usize = TABLE_SIZE;
-const CTAB1_SPECIAL_SIZE: usize = 5;
-const CTAB2_SIZE: usize = 27;
-const CTAB2_SPECIAL_SIZE: usize = 7;
-const CNTT_SIZE: usize = 2;
+use self::layout::{CtabLayout, TABLE_SIZE};
+
+/// Byte layout of the CTAB chunks, shared by the reader and the writer.
+///
+/// Both versions start with the same 20 bytes, described by the constants of this module.
+/// The version specific data that follows is described by [`CtabLayout`].
+pub(crate) mod layout {
+    use core::ops::Range;
+
+    /// Midi source channel.
+    pub(crate) const SOURCE: usize = 0;
+    /// Name, padded with spaces.
+    pub(crate) const NAME: Range<usize> = 1..9;
+    /// Accompaniment midi channel.
+    pub(crate) const DEST: usize = 9;
+    /// Editable flag, `0` when editable.
+    pub(crate) const EDITABLE: usize = 10;
+    /// Note mute bits.
+    pub(crate) const NOTE_MUTE: Range<usize> = 11..13;
+    /// Chord mute bits.
+    pub(crate) const CHORD_MUTE: Range<usize> = 13..18;
+    /// Root of the source chord.
+    pub(crate) const SOURCE_CHORD: usize = 18;
+    /// Type of the source chord.
+    pub(crate) const SOURCE_CHORD_TYPE: usize = 19;
+    /// Size of the part shared by both versions.
+    pub(crate) const COMMON_SIZE: usize = 20;
+
+    /// Size of a single transposition table.
+    pub(crate) const TABLE_SIZE: usize = 6;
+    /// Size of the special bytes of a `Ctab` chunk, presence flag included.
+    pub(crate) const CTAB1_SPECIAL_SIZE: usize = 5;
+    /// Size of the special bytes of a `Ctb2` chunk.
+    pub(crate) const CTAB2_SPECIAL_SIZE: usize = 7;
+
+    /// Offsets of the version specific part of a CTAB, following the common part.
+    pub(crate) struct CtabLayout {
+        /// Lowest and highest notes of the mid range. SFFv2 only.
+        pub(crate) range: Option<Range<usize>>,
+        /// Transposition tables.
+        pub(crate) tables: Range<usize>,
+        /// Special bytes.
+        ///
+        /// In SFFv1 the first special byte is a flag: when it is `0`, the chunk ends there.
+        pub(crate) special: Range<usize>,
+    }
+
+    impl CtabLayout {
+        /// Layout of a `Ctab` chunk.
+        pub(crate) const V1: CtabLayout = CtabLayout {
+            range: None,
+            tables: COMMON_SIZE..COMMON_SIZE + TABLE_SIZE,
+            special: COMMON_SIZE + TABLE_SIZE..COMMON_SIZE + TABLE_SIZE + CTAB1_SPECIAL_SIZE,
+        };
+
+        /// Layout of a `Ctb2` chunk.
+        pub(crate) const V2: CtabLayout = CtabLayout {
+            range: Some(COMMON_SIZE..COMMON_SIZE + 2),
+            tables: COMMON_SIZE + 2..COMMON_SIZE + 2 + TABLE_SIZE * 3,
+            special: COMMON_SIZE + 2 + TABLE_SIZE * 3
+                ..COMMON_SIZE + 2 + TABLE_SIZE * 3 + CTAB2_SPECIAL_SIZE,
+        };
+
+        /// Number of transposition tables.
+        pub(crate) fn table_count(&self) -> usize {
+            self.tables.len() / TABLE_SIZE
+        }
+
+        /// Size of the whole chunk, special bytes included.
+        pub(crate) fn size(&self) -> usize {
+            self.special.end
+        }
+    }
+}
 
 /// There are two types of CTAB chunks:
 /// - Ctab1: oldest. May be associated with a CNTT chunk.
 /// - Ctab2: All in one. No CNTT.
+///
 /// Ctab1 and Ctab2 share the same structure for their first 20 bytes.
 /// An additional variant may be present in SFFv2: [`Version::Guitar`].
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub(crate) enum Version {
     Ctab1,
     Ctab2,
@@ -26,8 +99,23 @@ pub(crate) enum Version {
     Guitar,
 }
 
-#[derive(Debug)]
-pub(crate) struct Ctab<'a> {
+impl Version {
+    pub(crate) fn layout(self) -> &'static CtabLayout {
+        match self {
+            Version::Ctab1 => &CtabLayout::V1,
+            Version::Ctab2 | Version::Guitar => &CtabLayout::V2,
+        }
+    }
+}
+
+/// An accompaniment table that does not borrow from the parsed bytes, as returned by
+/// [`Ctab::into_owned`].
+pub type OwnedCtab = Ctab<'static>;
+
+/// An accompaniment table (CTAB), describing how a source channel of the style is played back
+/// depending on the chords played by the performer.
+#[derive(Clone)]
+pub struct Ctab<'a> {
     /// Midi source channel: 0x00 (channel 1) to 0x0F (channel 16)
     source: u4,
     // name is padded with spaces (0x20) if smaller than 8 bytes
@@ -67,11 +155,485 @@ pub(crate) struct Ctab<'a> {
     /// lowest and highest notes of the middle range (inclusive). Only usefull for SFFv2.
     range: (u7, u7),
     /// the meaning of those bytes is not known
-    special: Option<&'a [u8]>,
+    special: Option<Cow<'a, [u8]>>,
+    /// Whether the special bytes were complete, or the chunk ended before them
+    trailer_present: bool,
 }
 
-impl Ctab<'_> {
-    pub(crate) fn read(chunk: Chunk) -> Result<Ctab> {
+impl fmt::Debug for Ctab<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The mute maps are printed in chromatic / chord enum order, so that the output does not
+        // depend on the HashMap iteration order.
+        struct Ordered<'m, K: 'static>(&'m HashMap<K, bool>, &'static [K]);
+        impl<K: fmt::Debug + Eq + core::hash::Hash> fmt::Debug for Ordered<'_, K> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.1.iter().filter_map(|k| self.0.get(k).map(|v| (k, v))))
+                    .finish()
+            }
+        }
+        f.debug_struct("Ctab")
+            .field("source", &self.source)
+            .field("name", &self.name)
+            .field("dest", &self.dest)
+            .field("editable", &self.editable)
+            .field("note_mute", &Ordered(&self.note_mute, &Key::ALL))
+            .field("chord_mute", &Ordered(&self.chord_mute, &Chord::ALL))
+            .field("source_chord", &self.source_chord)
+            .field("source_chord_type", &self.source_chord_type)
+            .field("table", &self.table)
+            .field("range", &self.range)
+            .field("special", &self.special)
+            .field("trailer_present", &self.trailer_present)
+            .finish()
+    }
+}
+
+impl<'a> Ctab<'a> {
+    /// Midi source channel, as recorded in the MIDI section of the style.
+    pub fn source(&self) -> u4 {
+        self.source
+    }
+
+    /// Name of the table, with its space padding removed.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Accompaniment midi channel the source channel is played back on.
+    pub fn dest(&self) -> u4 {
+        self.dest
+    }
+
+    /// Midi source channel as a plain `0..=15` value.
+    pub fn source_channel_u8(&self) -> u8 {
+        self.source.as_int()
+    }
+
+    /// Accompaniment midi channel as a plain `0..=15` value.
+    pub fn dest_channel_u8(&self) -> u8 {
+        self.dest.as_int()
+    }
+
+    /// Midi source channel as a human channel number, from `1` (Ch1) to `16` (Ch16).
+    pub fn source_channel_number(&self) -> u8 {
+        self.source.as_int() + 1
+    }
+
+    /// Accompaniment midi channel as a human channel number, from `1` (Ch1) to `16` (Ch16).
+    pub fn dest_channel_number(&self) -> u8 {
+        self.dest.as_int() + 1
+    }
+
+    /// Change the accompaniment midi channel, given as a human channel number, so that the source
+    /// channel is played back as another [accompaniment part](Ctab::dest_role).
+    ///
+    /// Fails if `channel` is not an accompaniment channel, from `9` (Ch9) to `16` (Ch16).
+    pub fn set_dest_channel(&mut self, channel: u8) -> Result<()> {
+        ensure!(
+            (9..=16).contains(&channel),
+            err_invalid!("not an accompaniment channel")
+        );
+        self.dest = u4::new(channel - 1);
+        Ok(())
+    }
+
+    /// Accompaniment part the source channel was recorded as, in the MIDI section of the style.
+    ///
+    /// Returns `None` if the source channel is not an accompaniment channel (Ch9..Ch16).
+    pub fn source_role(&self) -> Option<AccompanimentPart> {
+        AccompanimentPart::from_channel(self.source)
+    }
+
+    /// Accompaniment part the source channel is played back as.
+    ///
+    /// Returns `None` if the destination channel is not an accompaniment channel (Ch9..Ch16).
+    pub fn dest_role(&self) -> Option<AccompanimentPart> {
+        AccompanimentPart::from_channel(self.dest)
+    }
+
+    /// Whether the source channel data is editable.
+    pub fn editable(&self) -> bool {
+        self.editable
+    }
+
+    /// Whether the track is muted when a chord with the given root note is played.
+    pub fn is_note_muted(&self, key: Key) -> bool {
+        self.note_mute.get(&key).copied().unwrap_or(false)
+    }
+
+    /// Whether the track is muted when the given type of chord is played.
+    pub fn is_chord_muted(&self, chord: Chord) -> bool {
+        !self.chord_mute.get(&chord).copied().unwrap_or(true)
+    }
+
+    /// Whether the track plays when the given type of chord is played.
+    ///
+    /// This is the opposite of [`Ctab::is_chord_muted`].
+    pub fn plays_chord(&self, chord: Chord) -> bool {
+        !self.is_chord_muted(chord)
+    }
+
+    /// Whether the track plays when a chord with the given root and type is played, that is when
+    /// neither the root note nor the chord type is muted.
+    ///
+    /// With [`Chord::Cancel`] no chord is played, so `root` is ignored: the chord mute field has
+    /// no bit for it, so nothing in the table mutes the track and `true` is returned.
+    /// The special flags of the chord mute field, such as [`Chord::SpecialAutostart`], are not
+    /// played chords and always return `false`.
+    pub fn plays(&self, root: Key, chord: Chord) -> bool {
+        match chord {
+            Chord::SpecialAutostart | Chord::SpecialPercussion => false,
+            Chord::Cancel => true,
+            _ => !self.is_note_muted(root) && !self.is_chord_muted(chord),
+        }
+    }
+
+    /// Mute the track for every root note.
+    pub fn mute_all_notes(&mut self) {
+        self.note_mute = Key::ALL.iter().map(|&key| (key, true)).collect();
+    }
+
+    /// Play the track for every root note.
+    pub fn unmute_all_notes(&mut self) {
+        self.note_mute = Key::ALL.iter().map(|&key| (key, false)).collect();
+    }
+
+    /// Mute the track for every chord type.
+    ///
+    /// [`Chord::Cancel`] and the special flags of the chord mute field, such as
+    /// [`Ctab::drums_autostart`], are not chord types and are left unchanged.
+    pub fn mute_all_chords(&mut self) {
+        self.set_all_chords(false);
+    }
+
+    /// Play the track for every chord type.
+    ///
+    /// As with [`Ctab::mute_all_chords`], the special flags are left unchanged.
+    pub fn unmute_all_chords(&mut self) {
+        self.set_all_chords(true);
+    }
+
+    // The chord mute map stores whether each chord plays.
+    fn set_all_chords(&mut self, plays: bool) {
+        for &chord in Chord::ALL.iter().filter(|chord| chord.is_chord_type()) {
+            self.chord_mute.insert(chord, plays);
+        }
+    }
+
+    /// Whether the track plays or is muted, for every root note and chord type.
+    ///
+    /// Cells are ordered by root note (chromatically from C) then by chord type, as
+    /// `(root, chord, plays)`.
+    /// [`Chord::Cancel`] and the special flags of the chord mute field are not chord types, and
+    /// are not included.
+    pub fn response_matrix(&self) -> Vec<(Key, Chord, bool)> {
+        Key::ALL
+            .iter()
+            .flat_map(|&root| {
+                Chord::ALL
+                    .iter()
+                    .filter(|chord| chord.is_chord_type())
+                    .map(move |&chord| (root, chord, self.plays(root, chord)))
+            })
+            .collect()
+    }
+
+    /// The [intervals](Chord::intervals) of every chord type the track plays, that is the chord
+    /// shapes this table responds to, in the order of the [`Chord`] variants.
+    ///
+    /// Only the chord mutes are considered, see [`Ctab::plays_chord`]; the root note mutes are
+    /// left to the caller.
+    pub fn played_chord_intervals(&self) -> Vec<&'static [u8]> {
+        Chord::ALL
+            .iter()
+            .filter(|chord| chord.is_chord_type() && self.plays_chord(**chord))
+            .map(Chord::intervals)
+            .collect()
+    }
+
+    /// Key of the chord the source channel was recorded in.
+    pub fn source_chord(&self) -> Key {
+        self.source_chord
+    }
+
+    /// Type of the chord the source channel was recorded in.
+    pub fn source_chord_type(&self) -> Chord {
+        self.source_chord_type
+    }
+
+    /// The MIDI notes of the chord the source channel was recorded in, in close position from
+    /// its root in the given octave.
+    ///
+    /// Octaves are numbered as in [`pitch_name`], so that the root of a C chord in octave 4 is
+    /// the middle C (note 60). The notes are [`Chord::intervals`] above the root, and those above
+    /// the MIDI range are left out. [`Chord::Cancel`] and the special flags have no notes.
+    pub fn source_voicing(&self, octave: u8) -> Vec<u7> {
+        let root = (u16::from(octave) + 1) * 12 + u16::from(self.source_chord as u8);
+        self.source_chord_type
+            .intervals()
+            .iter()
+            .map(|&interval| root + u16::from(interval))
+            .filter_map(|note| u8::try_from(note).ok().and_then(u7::try_from))
+            .collect()
+    }
+
+    /// Note transposition tables.
+    ///
+    /// SFFv1 tables have a single table for the whole note range, SFFv2 tables have one table for
+    /// each of the low, mid and high note ranges.
+    pub fn tables(&self) -> &[Table] {
+        &self.table
+    }
+
+    /// Whether any of the transposition tables has [bass mode](Table::bass_on) on.
+    pub fn has_bass_table(&self) -> bool {
+        self.table.iter().any(Table::bass_on)
+    }
+
+    /// Lowest and highest notes of the middle range (inclusive).
+    ///
+    /// Always covers the full note range for SFFv1 tables.
+    pub fn range(&self) -> (u7, u7) {
+        self.range
+    }
+
+    /// Number of transposition zones, each one with its own table.
+    ///
+    /// SFFv1 tables always have a single zone covering the notes `0..=127`, SFFv2 tables have a
+    /// low, a mid and a high zone.
+    /// Outside of strict mode, SFFv2 tables carrying more tables are read too: the extra ones are
+    /// counted here and written back as read, but only the first three are used for the zones.
+    pub fn table_count(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Lowest and highest notes of the mid zone (inclusive), which are the boundaries between
+    /// the low, mid and high zones.
+    ///
+    /// Returns `None` for SFFv1 tables, whose single zone covers the notes `0..=127`.
+    pub fn range_boundaries(&self) -> Option<(u7, u7)> {
+        match self.version() {
+            Version::Ctab1 => None,
+            Version::Ctab2 | Version::Guitar => Some(self.range),
+        }
+    }
+
+    /// The note spans of the transposition zones, from low to high (inclusive).
+    ///
+    /// SFFv1 tables have a single zone covering the notes `0..=127`. SFFv2 tables have a low
+    /// zone below [`Ctab::range_boundaries`], the mid zone and a high zone above it, each
+    /// paired with the table of the same index in [`Ctab::tables`].
+    /// A low or high zone which would hold no note, because the mid zone starts at note 0 or
+    /// ends at note 127, is left out: use [`Ctab::table_for_note`] with the first note of a zone
+    /// to find its table in that case.
+    pub fn zones(&self) -> Vec<(u7, u7)> {
+        let (low, high) = match self.range_boundaries() {
+            Some(range) => range,
+            None => return vec![(u7::new(0), u7::max_value())],
+        };
+        let mut zones = Vec::with_capacity(3);
+        if low > u7::new(0) {
+            zones.push((u7::new(0), u7::new(low.as_int() - 1)));
+        }
+        zones.push((low, high));
+        if high < u7::max_value() {
+            zones.push((u7::new(high.as_int() + 1), u7::max_value()));
+        }
+        zones
+    }
+
+    /// Mutable access to the transposition table of a zone.
+    ///
+    /// SFFv1 tables only have the [`RangeSlot::Full`] zone, and SFFv2 tables only the
+    /// [`RangeSlot::Low`], [`RangeSlot::Mid`] and [`RangeSlot::High`] ones: `None` is returned
+    /// for the other zones.
+    pub fn table_mut(&mut self, slot: RangeSlot) -> Option<&mut Table> {
+        let index = match (self.version(), slot) {
+            (Version::Ctab1, RangeSlot::Full) => 0,
+            (Version::Ctab1, _) | (_, RangeSlot::Full) => return None,
+            (_, RangeSlot::Low) => 0,
+            (_, RangeSlot::Mid) => 1,
+            (_, RangeSlot::High) => 2,
+        };
+        self.table.get_mut(index)
+    }
+
+    /// Change the lowest and highest notes of the mid zone (inclusive), see
+    /// [`Ctab::range_boundaries`].
+    ///
+    /// Fails for SFFv1 tables, which have a single zone covering every note, and if `low` is
+    /// above `high`.
+    pub fn set_range(&mut self, low: u7, high: u7) -> Result<()> {
+        ensure!(
+            self.version() != Version::Ctab1,
+            err_invalid!("SFFv1 tables have no mid zone")
+        );
+        ensure!(
+            low <= high,
+            err_invalid!("note range low limit above high limit")
+        );
+        self.range = (low, high);
+        Ok(())
+    }
+
+    /// The transposition table applying to a source note.
+    ///
+    /// Notes below the mid zone use the low table, notes above it use the high table.
+    pub fn table_for_note(&self, note: u7) -> &Table {
+        match self.range_boundaries() {
+            Some((low, _)) if note < low => &self.table[0],
+            Some((_, high)) if note > high => &self.table[2],
+            Some(_) => &self.table[1],
+            None => &self.table[0],
+        }
+    }
+
+    /// Transpose the source notes of a melody to follow a played chord.
+    ///
+    /// Each note is transposed with the table of its zone, see [`Ctab::table_for_note`] and
+    /// [`Table::transpose`]: as there, only the root of the chord is followed. The tables are
+    /// given the interval from the [source chord](Ctab::source_chord) to `chord_root`, as if the
+    /// source was recorded in C, and their [high key](Table::high_key) is taken relative to the
+    /// source chord as well, as [`Ctab::rekey`] moves it along with the source.
+    /// If the track does not [play](Ctab::plays) the chord, because its root or its type is
+    /// muted, no note is returned.
+    pub fn harmonize(&self, melody: &[u7], chord_root: Key, chord: Chord) -> Vec<u7> {
+        if !self.plays(chord_root, chord) {
+            return Vec::new();
+        }
+        melody
+            .iter()
+            .map(|&note| {
+                self.table_for_note(note)
+                    .transpose_from(note, chord_root, self.source_chord)
+            })
+            .collect()
+    }
+
+    /// What to do with the sounding source notes `playing` when the played chord changes to
+    /// `new_root` and `new_chord`, with one action per note, in order.
+    ///
+    /// Each note follows the [retrigger rule](Table::retrigger_rule) of the table of its zone,
+    /// see [`Ctab::table_for_note`]:
+    /// * [`RetriggerRule::Stop`] stops the note;
+    /// * [`RetriggerRule::PitchShift`] and [`RetriggerRule::Retrigger`] shift or retrigger the
+    ///   note at its pitch in the new chord, as given by [`Table::transpose`] with the interval
+    ///   from the [source chord](Ctab::source_chord) to `new_root`, as in [`Ctab::harmonize`];
+    /// * [`RetriggerRule::PitchShiftToRoot`] and [`RetriggerRule::RetriggerToRoot`] shift or
+    ///   retrigger the note at the nearest pitch of the new root, kept within the note range of
+    ///   the table;
+    /// * [`RetriggerRule::NoteGenerator`] generates notes from the chord, which is not modelled
+    ///   here: the note is retriggered at its pitch in the new chord.
+    ///
+    /// Every note is stopped if the track does not [play](Ctab::plays) the new chord.
+    pub fn on_chord_change(
+        &self,
+        playing: &[u7],
+        new_root: Key,
+        new_chord: Chord,
+    ) -> Vec<NoteAction> {
+        let plays = self.plays(new_root, new_chord);
+        playing
+            .iter()
+            .map(|&note| {
+                let table = self.table_for_note(note);
+                let pitch = table.transpose_from(note, new_root, self.source_chord);
+                // Nearest pitch of the new root, from a fourth below to a tritone above.
+                // The pitch sounds in the played key, so the root is not relative to the source.
+                let root = {
+                    let offset = (new_root as i16 - i16::from(pitch.as_int() % 12)).rem_euclid(12);
+                    let offset = if offset > 6 { offset - 12 } else { offset };
+                    table.clamp_note(i16::from(pitch.as_int()) + offset)
+                };
+                match table.retrigger_rule {
+                    _ if !plays => NoteAction::Stop,
+                    RetriggerRule::Stop => NoteAction::Stop,
+                    RetriggerRule::PitchShift => NoteAction::Shift(pitch),
+                    RetriggerRule::PitchShiftToRoot => NoteAction::Shift(root),
+                    RetriggerRule::Retrigger | RetriggerRule::NoteGenerator => {
+                        NoteAction::Retrigger(pitch)
+                    }
+                    RetriggerRule::RetriggerToRoot => NoteAction::Retrigger(root),
+                }
+            })
+            .collect()
+    }
+
+    /// Trailing bytes of unknown meaning.
+    pub fn special(&self) -> Option<&[u8]> {
+        self.special.as_deref()
+    }
+
+    /// Whether the special bytes were complete in the file.
+    ///
+    /// SFFv2 tables always end with 7 special bytes, and SFFv1 tables with a flag byte followed,
+    /// if the flag is set, by 4 more bytes.
+    /// Outside of strict mode, tables cut short before those bytes are still read, with no
+    /// [special](Ctab::special) bytes: such tables are not written back as they were read.
+    pub fn trailer_present(&self) -> bool {
+        self.trailer_present
+    }
+
+    /// Whether both tables play their source channel back the same way.
+    ///
+    /// The source and destination channels, the note and chord mutes (including the drums
+    /// autostart flag), the source chord, the middle note range and the transposition tables are
+    /// compared. The name, the [editable](Ctab::editable) flag and the [special](Ctab::special)
+    /// bytes only matter to editors, and are ignored.
+    pub fn equivalent_for_playback(&self, other: &Ctab) -> bool {
+        self.source == other.source
+            && self.dest == other.dest
+            && Key::ALL
+                .iter()
+                .all(|&key| self.is_note_muted(key) == other.is_note_muted(key))
+            && Chord::ALL
+                .iter()
+                .all(|&chord| self.is_chord_muted(chord) == other.is_chord_muted(chord))
+            && self.source_chord == other.source_chord
+            && self.source_chord_type == other.source_chord_type
+            && self.range == other.range
+            && self.table == other.table
+    }
+
+    // Feed the fields compared by `equivalent_for_playback` to a hasher.
+    // Only bytes are written, so that the result does not depend on the platform.
+    pub(crate) fn hash_playback<H: Hasher>(&self, state: &mut H) {
+        state.write(&[self.source.as_int(), self.dest.as_int()]);
+        state.write(&self.encode_note_mute());
+        for &chord in Chord::ALL.iter() {
+            state.write(&[self.is_chord_muted(chord) as u8]);
+        }
+        state.write(&[
+            self.source_chord as u8,
+            self.source_chord_type as u8,
+            self.range.0.as_int(),
+            self.range.1.as_int(),
+            self.table.len() as u8,
+        ]);
+        for table in &self.table {
+            state.write(&[
+                table.ntr as u8,
+                table.ntt as u8,
+                table.bass_on as u8,
+                table.v1_bass_flag as u8,
+                table.high_key as u8,
+                table.note_range.0.as_int(),
+                table.note_range.1.as_int(),
+                table.range_flags.0 as u8,
+                table.range_flags.1 as u8,
+                table.retrigger_rule as u8,
+            ]);
+        }
+    }
+
+    // Read with the default options.
+    #[cfg(test)]
+    pub(crate) fn read(chunk: Chunk<'a>) -> Result<Ctab<'a>> {
+        Ctab::read_with(chunk, &ParseOptions::default())
+    }
+
+    pub(crate) fn read_with(chunk: Chunk<'a>, options: &ParseOptions) -> Result<Ctab<'a>> {
         let version: Version;
         let mut value = match chunk {
             Chunk::Ctab1(v) => {
@@ -86,73 +648,90 @@ impl Ctab<'_> {
         };
 
         let source = u4::read(&mut value)?;
-        let name = match value.split_checked(8) {
-            Some(v) => match std::str::from_utf8(v) {
-                Ok(name) => name.trim().to_string(),
-                Err(_) => {
-                    if cfg!(feature = "strict") {
-                        bail!(err_malformed!("not a valid string for name"));
-                    } else {
-                        String::default()
-                    }
+        let name = match read_string(
+            value.take_checked(layout::NAME.len(), err_malformed!("missing name"))?,
+            Encoding::Utf8,
+            true,
+        ) {
+            Some(name) => name,
+            None => {
+                if options.strict() {
+                    bail!(err_malformed!("not a valid string for name"));
                 }
-            },
-            None => bail!(err_invalid!("name field is not a string")),
+                options.skip(err_malformed!("not a valid string for name"));
+                String::default()
+            }
         };
         let dest = u4::read(&mut value)?;
         let editable = u8::read(&mut value)? == 0;
-        let data = [u8::read(&mut value)?, u8::read(&mut value)?];
-        let note_mute = Ctab::read_note_mute(data)?;
-        let data = match value.split_checked(5) {
-            Some(v) => v.try_into().expect("array of size 5"),
-            None => bail!(err_invalid!("not enough data for chord mute")),
-        };
-        let chord_mute = Ctab::read_chord_mute(data)?;
+        let note_mute = Ctab::read_note_mute(
+            value.take_array(err_malformed!("missing note mute"))?,
+            options,
+        )?;
+        let chord_mute = Ctab::read_chord_mute(
+            value.take_array(err_malformed!("missing chord mute"))?,
+            options,
+        )?;
         let source_chord = Key::try_from(u8::read(&mut value)?)?;
-        let source_chord_type = Chord::try_from(u8::read(&mut value)?)?;
+        let source_chord_type = options.read_chord(u8::read(&mut value)?)?;
 
-        // table has at most 3 components
-        let mut table = Vec::with_capacity(3);
+        let layout = version.layout();
         // full midi note's range by default for CTABv1
         let mut range = (u7::from(0), u7::from(127));
-        let special;
-        match version {
-            Version::Ctab2 | Version::Guitar => {
-                range = (u7::read(&mut value)?, u7::read(&mut value)?);
-                if let Some(data) = value.split_checked(TABLE_SIZE * 3) {
-                    let low = Table::try_from((&data[..TABLE_SIZE], Version::Ctab2))?;
-                    table.push(low);
-                    let mid = Table::try_from((&data[TABLE_SIZE..TABLE_SIZE * 2], Version::Ctab2))?;
-                    table.push(mid);
-                    let high =
-                        Table::try_from((&data[TABLE_SIZE * 2..TABLE_SIZE * 3], Version::Ctab2))?;
-                    table.push(high);
-                } else {
-                    bail!(err_malformed!("cannot construct transposition table"));
-                }
-
-                special = value.split_checked(CTAB2_SPECIAL_SIZE);
-                if special.is_none() && cfg!(feature = "strict") {
-                    bail!(err_malformed!("missing special bytes at the end of CTABv2"));
-                }
+        if layout.range.is_some() {
+            range = (u7::read(&mut value)?, u7::read(&mut value)?);
+        }
+        // single table for CTABv1, low, mid and high tables for CTABv2
+        let (available, trailer) = Ctab::table_count_in(value, version);
+        if available > layout.table_count() {
+            if options.strict() {
+                bail!(err_malformed!("unexpected number of transposition tables"));
             }
-            Version::Ctab1 => {
-                if let Some(data) = value.split_checked(TABLE_SIZE) {
-                    table.push(Table::try_from((data, Version::Ctab1))?);
-                } else {
-                    bail!(err_malformed!("cannot construct transposition table"));
-                }
-
-                if u8::read(&mut value)? != 0x00 {
-                    special = value.split_checked(CTAB1_SPECIAL_SIZE - 1);
-                    if special.is_none() && cfg!(feature = "strict") {
-                        bail!(err_malformed!("missing special bytes at the end of CTABv1"));
-                    }
-                } else {
-                    special = None;
-                }
+            options.skip(err_malformed!("unexpected number of transposition tables"));
+        }
+        let data = value.take_checked(
+            available * TABLE_SIZE,
+            err_malformed!("cannot construct transposition table"),
+        )?;
+        let mut table = data
+            .chunks(TABLE_SIZE)
+            .map(|data| Table::read_with(data, version, options))
+            .collect::<Result<Vec<_>>>()?;
+        // Outside of strict mode, missing tables are replaced by default ones
+        if available < layout.table_count() {
+            if options.strict() {
+                bail!(err_malformed!("cannot construct transposition table"));
             }
+            options.skip(err_malformed!("missing transposition tables"));
+            if !trailer {
+                // The rest is a partial table, not the special bytes
+                value = &[];
+            }
+            table.resize(layout.table_count(), Table::default());
         }
+        let (special, trailer_present) = match version {
+            Version::Ctab2 | Version::Guitar => {
+                let special = value.take_trailer(
+                    layout.special.len(),
+                    err_malformed!("missing special bytes at the end of CTABv2"),
+                    options,
+                )?;
+                (special, special.is_some())
+            }
+            // The special bytes are only present if their first byte is not 0
+            Version::Ctab1 => match value.first() {
+                Some(0x00) => (None, true),
+                Some(_) => {
+                    let special = value.take_trailer(
+                        layout.special.len(),
+                        err_malformed!("missing special bytes at the end of CTABv1"),
+                        options,
+                    )?;
+                    (special, special.is_some())
+                }
+                None => bail!(err_malformed!("missing special flag at the end of CTABv1")),
+            },
+        };
 
         Ok(Ctab {
             source,
@@ -165,14 +744,299 @@ impl Ctab<'_> {
             source_chord_type,
             table,
             range,
-            special,
+            special: special.map(Cow::Borrowed),
+            trailer_present,
         })
     }
 
-    fn read_note_mute(value: [u8; 2]) -> Result<HashMap<Key, bool>> {
+    /// Write this table as a `Ctab` chunk for SFFv1 tables, or a `Ctb2` chunk for SFFv2 ones.
+    ///
+    /// Missing SFFv2 special bytes are written as zeros.
+    pub fn write<W: Write>(&self, out: &mut W) -> WriteResult<W> {
+        let id = match self.version() {
+            Version::Ctab1 => b"Ctab",
+            Version::Ctab2 | Version::Guitar => b"Ctb2",
+        };
+        let data = self.encode().map_err(W::invalid_input)?;
+        write_chunk(id, &data, out)
+    }
+
+    /// Encode the content of the CTAB chunk, as laid out by [`layout`].
+    ///
+    /// Extra SFFv2 tables, read outside of strict mode, are written back between the three
+    /// tables of the layout and the special bytes.
+    pub(crate) fn encode(&self) -> StdResult<Vec<u8>, &'static str> {
+        let version = self.version();
+        let layout = version.layout();
+        let extra = match self.table.len().checked_sub(layout.table_count()) {
+            Some(extra) if extra == 0 || version != Version::Ctab1 => extra * TABLE_SIZE,
+            _ => return Err("unexpected number of transposition tables"),
+        };
+        let tables = layout.tables.start..layout.tables.end + extra;
+        let special = layout.special.start + extra..layout.special.end + extra;
+        let mut raw = vec![0; layout.size() + extra];
+        raw[layout::SOURCE] = self.source.as_int();
+        // The name is padded with spaces
+        let name = &mut raw[layout::NAME];
+        let len = self.name.len().min(name.len());
+        name.fill(b' ');
+        name[..len].copy_from_slice(&self.name.as_bytes()[..len]);
+        raw[layout::DEST] = self.dest.as_int();
+        raw[layout::EDITABLE] = if self.editable { 0x00 } else { 0x01 };
+        raw[layout::NOTE_MUTE].copy_from_slice(&self.encode_note_mute());
+        raw[layout::CHORD_MUTE].copy_from_slice(&self.encode_chord_mute());
+        raw[layout::SOURCE_CHORD] = self.source_chord as u8;
+        raw[layout::SOURCE_CHORD_TYPE] = match self.source_chord_type {
+            Chord::SpecialAutostart | Chord::SpecialPercussion => {
+                return Err("source chord type is not a chord")
+            }
+            chord => chord as u8,
+        };
+        if let Some(range) = layout.range.clone() {
+            raw[range].copy_from_slice(&[self.range.0.as_int(), self.range.1.as_int()]);
+        }
+        for (table, data) in self.table.iter().zip(raw[tables].chunks_mut(TABLE_SIZE)) {
+            data.copy_from_slice(&table.encode(version)?);
+        }
+        match (self.special(), version) {
+            (Some(bytes), _) if bytes.len() == special.len() => raw[special].copy_from_slice(bytes),
+            (Some(_), _) => return Err("unexpected number of special bytes"),
+            // A single 0 flag stands for the missing special bytes
+            (None, Version::Ctab1) => raw.truncate(special.start + 1),
+            (None, _) => {}
+        }
+        Ok(raw)
+    }
+
+    // Number of whole transposition tables in `value`, the rest of the chunk after the note
+    // range, and whether they are followed by the special bytes.
+    //
+    // SFFv1 tables always have a single table. SFFv2 tables are expected to have three, but
+    // when the rest of the chunk is exactly a whole number of tables followed by the special
+    // bytes, the count is derived from its length, so that variants with more tables can still
+    // be read outside of strict mode. The low, mid and high tables are the first three.
+    // Tables cut short keep the special bytes when they are intact, as long as the chunk is too
+    // short to hold three tables. Any other length is read as at most three tables followed by a
+    // damaged trailer, rather than reading part of the trailer as another table.
+    fn table_count_in(value: &[u8], version: Version) -> (usize, bool) {
+        let expected = version.layout().table_count();
+        match version {
+            Version::Ctab1 => {
+                let count = expected.min(value.len() / TABLE_SIZE);
+                (count, count == expected)
+            }
+            Version::Ctab2 | Version::Guitar => {
+                let whole = value.len() / TABLE_SIZE;
+                match value.len().checked_sub(layout::CTAB2_SPECIAL_SIZE) {
+                    // More tables than expected, or fewer ones with intact special bytes
+                    Some(tables) if tables % TABLE_SIZE == 0 => {
+                        let count = tables / TABLE_SIZE;
+                        if count >= expected || whole < expected {
+                            (count, true)
+                        } else {
+                            (expected, false)
+                        }
+                    }
+                    _ => (expected.min(whole), false),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn encode_note_mute(&self) -> [u8; 2] {
+        // Muted notes are set to 0, from C on the LSB of the second byte.
+        let mut bits = 0_u16;
+        for (bit, key) in Key::ALL.iter().enumerate() {
+            if !self.is_note_muted(*key) {
+                bits |= 1 << bit;
+            }
+        }
+        bits.to_be_bytes()
+    }
+
+    pub(crate) fn encode_chord_mute(&self) -> [u8; 5] {
+        let mut value = [0; 5];
+        // Inverse of `read_chord_mute`
+        for (cur, chord) in CHORD_MUTE_ORDER.iter().enumerate() {
+            if self.chord_mute.get(chord).copied().unwrap_or(false) {
+                value[(cur + 4) / 8] |= 1 << (7 - (cur + 4) % 8);
+            }
+        }
+        value
+    }
+
+    /// Convert a SFFv1 table to its SFFv2 equivalent.
+    ///
+    /// The conversion rules are:
+    /// * the single transposition table is used for each of the low, mid and high note ranges;
+    /// * the middle range covers all the notes, so that the mid table applies everywhere and the
+    ///   low and high tables are never used;
+    /// * the SFFv1 only [`TranspositionTable::Bass`] becomes [`TranspositionTable::Melody`]
+    ///   with bass mode on, which is how SFFv2 expresses bass lines;
+    /// * the SFFv1 special bytes are dropped, and the SFFv2 ones are zeroed;
+    /// * the meaningless [SFFv1 bass flag](Table::v1_bass_flag) is dropped, rather than turning
+    ///   bass mode on.
+    ///
+    /// SFFv2 tables are returned unchanged.
+    pub fn upgrade_to_v2(&self) -> Ctab<'a> {
+        if self.version() != Version::Ctab1 {
+            return self.clone();
+        }
+        let mut table = self.table[0].clone();
+        table.v1_bass_flag = false;
+        if table.ntt == TranspositionTable::Bass {
+            table.ntt = TranspositionTable::Melody;
+            table.bass_on = true;
+        }
+        Ctab {
+            table: vec![table.clone(), table.clone(), table],
+            range: (u7::new(0), u7::max_value()),
+            special: Some(Cow::Borrowed(&[0; layout::CTAB2_SPECIAL_SIZE])),
+            trailer_present: true,
+            ..self.clone()
+        }
+    }
+
+    /// Convert a SFFv2 table to its SFFv1 equivalent, along with the features that could not be
+    /// kept.
+    ///
+    /// The conversion rules are:
+    /// * the mid range table is used for the whole note range, the low and high tables are
+    ///   dropped ([`LostFeature::ZoneTables`] if they differ from the mid one);
+    /// * [`TranspositionTable::Melody`] with bass mode on becomes [`TranspositionTable::Bass`],
+    ///   bass mode is dropped for other tables ([`LostFeature::BassMode`]);
+    /// * SFFv2 only transposition tables are replaced with the closest SFFv1 one
+    ///   ([`LostFeature::TranspositionTable`]): the "5th" minor scales with their plain
+    ///   variant, any other one with [`TranspositionTable::Melody`];
+    /// * [`TranspositionType::Guitar`] is replaced with [`TranspositionType::RootTransposition`]
+    ///   ([`LostFeature::TranspositionType`]);
+    /// * the SFFv2 special bytes are dropped ([`LostFeature::Special`]).
+    ///
+    /// SFFv1 tables are returned unchanged, with no lost feature.
+    pub fn downgrade_to_v1(&self) -> (Ctab<'a>, Vec<LostFeature>) {
+        let mut lost = Vec::new();
+        if self.version() == Version::Ctab1 {
+            return (self.clone(), lost);
+        }
+        // Tables are stored as low, mid and high.
+        let mut table = self.table[1].clone();
+        if self.table.iter().any(|t| *t != table) {
+            lost.push(LostFeature::ZoneTables);
+        }
+        if table.bass_on {
+            if table.ntt == TranspositionTable::Melody {
+                table.ntt = TranspositionTable::Bass;
+            } else {
+                lost.push(LostFeature::BassMode);
+            }
+            table.bass_on = false;
+        }
+        let ntt = match table.ntt {
+            TranspositionTable::Bypass
+            | TranspositionTable::Melody
+            | TranspositionTable::Chord
+            | TranspositionTable::Bass
+            | TranspositionTable::MelodicMinor
+            | TranspositionTable::HarmonicMinor => table.ntt,
+            TranspositionTable::MelodicMinor5th => TranspositionTable::MelodicMinor,
+            TranspositionTable::HarmonicMinor5th => TranspositionTable::HarmonicMinor,
+            _ => TranspositionTable::Melody,
+        };
+        if ntt != table.ntt {
+            lost.push(LostFeature::TranspositionTable(table.ntt));
+            table.ntt = ntt;
+        }
+        if table.ntr == TranspositionType::Guitar {
+            lost.push(LostFeature::TranspositionType(table.ntr));
+            table.ntr = TranspositionType::RootTransposition;
+        }
+        if self.special.is_some() {
+            lost.push(LostFeature::Special);
+        }
+        let ctab = Ctab {
+            table: vec![table],
+            range: (u7::new(0), u7::max_value()),
+            special: None,
+            trailer_present: true,
+            ..self.clone()
+        };
+        (ctab, lost)
+    }
+
+    /// Copy any data borrowed from the parsed bytes, so that the table can outlive them.
+    pub fn into_owned(self) -> OwnedCtab {
+        Ctab {
+            special: self.special.map(|special| Cow::Owned(special.into_owned())),
+            ..self
+        }
+    }
+
+    /// Move the whole table to a source recorded in `new_source_chord` instead of
+    /// [`source_chord`](Ctab::source_chord), as when the source channel is transposed.
+    ///
+    /// Everything the table expresses relative to the source is moved by the same interval, so
+    /// that it behaves with the transposed source as it did with the original one:
+    /// * the [muted root notes](Ctab::is_note_muted) and the [high key](Table::high_key) of
+    ///   each table are moved by the interval, wrapping around the octave;
+    /// * the [note range](Table::note_range) of each table and the boundaries of the
+    ///   [mid zone](Ctab::range) are moved by the interval, and clamped to `0..=127`.
+    ///
+    /// The interval is the smallest one from the old key to the new one, that is up to a fifth
+    /// down or a tritone up, to keep notes from being clamped. The chord type, chord mutes and
+    /// transposition rules are left unchanged, as they do not depend on the key.
+    pub fn rekey(&mut self, new_source_chord: Key) {
+        let shift = (new_source_chord as i8 - self.source_chord as i8).rem_euclid(12);
+        let shift = if shift > 6 { shift - 12 } else { shift };
+        let shift_note =
+            |note: u7| u7::new((i16::from(note.as_int()) + i16::from(shift)).clamp(0, 127) as u8);
+
+        self.source_chord = new_source_chord;
+        self.note_mute = self
+            .note_mute
+            .iter()
+            .map(|(&key, &muted)| (key.shifted(shift), muted))
+            .collect();
+        if self.version() != Version::Ctab1 {
+            self.range = (shift_note(self.range.0), shift_note(self.range.1));
+        }
+        for table in &mut self.table {
+            table.high_key = table.high_key.shifted(shift);
+            table.note_range = (
+                shift_note(table.note_range.0),
+                shift_note(table.note_range.1),
+            );
+        }
+    }
+
+    // SFFv1 tables have a single transposition table, SFFv2 ones have three.
+    pub(crate) fn version(&self) -> Version {
+        if self.table.len() == 1 {
+            Version::Ctab1
+        } else {
+            Version::Ctab2
+        }
+    }
+
+    /// Whether the drums start playing automatically at the start of the performance.
+    ///
+    /// This is the "enable autostart" bit of the chord mute field (bit 2 of its first byte),
+    /// which is stored as the [`Chord::SpecialAutostart`] entry of the chord mute table.
+    /// The neighbouring [`Chord::SpecialPercussion`] bit is also reserved to drums and
+    /// percussions, but its meaning is unknown and it does not affect the result.
+    pub fn drums_autostart(&self) -> bool {
+        self.chord_mute
+            .get(&Chord::SpecialAutostart)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    fn read_note_mute(value: [u8; 2], options: &ParseOptions) -> Result<HashMap<Key, bool>> {
         // The 4 MSB of the first byte are always 0.
-        if value[0] > 0b1111 && cfg!(feature = "strict") {
-            bail!(err_malformed!("note mute first nibble is not 0"));
+        if value[0] > 0b1111 {
+            if options.strict() {
+                bail!(err_malformed!("note mute first nibble is not 0"));
+            }
+            options.skip(err_malformed!("note mute first nibble is not 0"));
         }
         let b = value[0] & 0b1000 == 0;
         let bb = value[0] & 0b0100 == 0;
@@ -209,8 +1073,8 @@ impl Ctab<'_> {
     ///
     /// Chord Mute is encoded across five bytes:
     /// * Byte 1 \[0x00 .. 0xOF\]:
-    /// Bits 2 and 3 are only used for drums and percussions. When bit 2 is set to 1, auto play the
-    /// drums from the start of the performance.
+    ///   Bits 2 and 3 are only used for drums and percussions. When bit 2 is set to 1, auto play
+    ///   the drums from the start of the performance.
     ///     * bit 7 = 0 (unused)
     ///     * bit 6 = 0 (unused)
     ///     * bit 5 = 0 (unused)
@@ -255,58 +1119,18 @@ impl Ctab<'_> {
     ///     * Bit 2 = Maj7
     ///     * Bit 1 = Maj6
     ///     * Bit 0 = Maj
-    fn read_chord_mute(value: [u8; 5]) -> Result<HashMap<Chord, bool>> {
+    fn read_chord_mute(value: [u8; 5], options: &ParseOptions) -> Result<HashMap<Chord, bool>> {
         let mut chord_mute: HashMap<Chord, bool> = HashMap::with_capacity(CHORD_SIZE);
-        let chords_order = [
-            // byte 0 (First nibble is 0x0)
-            Chord::SpecialPercussion,
-            Chord::SpecialAutostart,
-            Chord::OnePlusTwoPlus5,
-            Chord::Sus4,
-            // byte 1
-            Chord::OnePlusFive,
-            Chord::OnePlusEight,
-            Chord::SevenAug,
-            Chord::Maj7aug,
-            Chord::SevenS9,
-            Chord::SevenB13,
-            Chord::SevenB9,
-            Chord::Seven13,
-            // byte 2
-            Chord::SevenS11,
-            Chord::Seven9,
-            Chord::SevenB5,
-            Chord::SevenSus4,
-            Chord::Seven,
-            Chord::Dim7,
-            Chord::Dim,
-            Chord::MinMaj7_9,
-            // byte 3
-            Chord::MinMaj7,
-            Chord::Min7_11,
-            Chord::Min7_9,
-            Chord::Min9,
-            Chord::Min7b5,
-            Chord::Min7,
-            Chord::Min6,
-            Chord::Min,
-            // byte 4
-            Chord::Aug,
-            Chord::Maj6_9,
-            Chord::Maj7_9,
-            Chord::Maj9,
-            Chord::Maj7s11,
-            Chord::Maj7,
-            Chord::Maj6,
-            Chord::Maj,
-        ];
         // The 4 MSB of the first byte are always 0.
-        if value[0] > 0b1111 && cfg!(feature = "strict") {
-            bail!(err_malformed!("first nibble of chord mute field is not 0"));
+        if value[0] > 0b1111 {
+            if options.strict() {
+                bail!(err_malformed!("first nibble of chord mute field is not 0"));
+            }
+            options.skip(err_malformed!("first nibble of chord mute field is not 0"));
         }
 
         // iterates over 5 bytes, except the 4 first bits of the first byte.
-        for (cur, chord) in chords_order.iter().enumerate() {
+        for (cur, chord) in CHORD_MUTE_ORDER.iter().enumerate() {
             // Cursor position within the current byte
             let pos = (cur + 4) % 8;
             // Current byte from `value`
@@ -322,23 +1146,213 @@ impl Ctab<'_> {
     }
 }
 
+/// A SFFv2 feature that cannot be represented in SFFv1, as reported by
+/// [`Ctab::downgrade_to_v1`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LostFeature {
+    /// The low and high range tables differ from the mid range one.
+    ZoneTables,
+    /// Bass mode was on for a table other than [`TranspositionTable::Melody`].
+    BassMode,
+    /// A SFFv2 only transposition table was used.
+    TranspositionTable(TranspositionTable),
+    /// A SFFv2 only transposition type was used.
+    TranspositionType(TranspositionType),
+    /// The special bytes at the end of the table.
+    Special,
+}
+
+/// The accompaniment parts of a style, each one played back on its own midi channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AccompanimentPart {
+    /// Ch9
+    SubRhythm,
+    /// Ch10
+    Rhythm,
+    /// Ch11
+    Bass,
+    /// Ch12
+    Chord1,
+    /// Ch13
+    Chord2,
+    /// Ch14
+    Pad,
+    /// Ch15
+    Phrase1,
+    /// Ch16
+    Phrase2,
+}
+
+impl AccompanimentPart {
+    /// The accompaniment part played back on a midi channel (`0` being Ch1).
+    ///
+    /// Returns `None` for channels Ch1 to Ch8, which are not accompaniment channels.
+    pub fn from_channel(channel: u4) -> Option<AccompanimentPart> {
+        Some(match channel.as_int() {
+            8 => AccompanimentPart::SubRhythm,
+            9 => AccompanimentPart::Rhythm,
+            10 => AccompanimentPart::Bass,
+            11 => AccompanimentPart::Chord1,
+            12 => AccompanimentPart::Chord2,
+            13 => AccompanimentPart::Pad,
+            14 => AccompanimentPart::Phrase1,
+            15 => AccompanimentPart::Phrase2,
+            _ => return None,
+        })
+    }
+
+    /// The midi channel this accompaniment part is played back on (`0` being Ch1).
+    pub fn channel(&self) -> u4 {
+        u4::new(*self as u8 + 8)
+    }
+}
+
 /// Standard keys used in style files
-#[derive(Debug, PartialEq, Hash, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Key {
+    /// C
     C,
+    /// C♯ / D♭
     Cs,
+    /// D
     D,
+    /// E♭ / D♯
     Eb,
+    /// E
     E,
+    /// F
     F,
+    /// F♯ / G♭
     Fs,
+    /// G
     G,
+    /// G♯ / A♭
     Gs,
+    /// A
     A,
+    /// B♭ / A♯
     Bb,
+    /// B
     B,
 }
 
+impl Key {
+    // The key `semitones` above this one, wrapping around the octave.
+    pub(crate) fn shifted(self, semitones: i8) -> Key {
+        Key::ALL[(self as i8 + semitones).rem_euclid(12) as usize]
+    }
+
+    /// All keys, in chromatic order starting from C.
+    pub(crate) const ALL: [Key; 12] = [
+        Key::C,
+        Key::Cs,
+        Key::D,
+        Key::Eb,
+        Key::E,
+        Key::F,
+        Key::Fs,
+        Key::G,
+        Key::Gs,
+        Key::A,
+        Key::Bb,
+        Key::B,
+    ];
+
+    /// The base letter of this key, ignoring its accidental.
+    ///
+    /// Keys are spelled as their variant names, so that [`Key::Eb`] is `'E'` and [`Key::Cs`] is
+    /// `'C'`.
+    pub fn letter(&self) -> char {
+        match self {
+            Key::C | Key::Cs => 'C',
+            Key::D => 'D',
+            Key::Eb | Key::E => 'E',
+            Key::F | Key::Fs => 'F',
+            Key::G | Key::Gs => 'G',
+            Key::A => 'A',
+            Key::Bb | Key::B => 'B',
+        }
+    }
+
+    /// The accidental of this key, or `None` for natural keys.
+    ///
+    /// Keys are spelled as their variant names, so that [`Key::Eb`] is flat and [`Key::Cs`] is
+    /// sharp.
+    pub fn accidental(&self) -> Option<Accidental> {
+        match self {
+            Key::Cs | Key::Fs | Key::Gs => Some(Accidental::Sharp),
+            Key::Eb | Key::Bb => Some(Accidental::Flat),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the letter and accidental of the key, such as `C♯` or `E♭`.
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.accidental() {
+            Some(Accidental::Sharp) => write!(f, "{}♯", self.letter()),
+            Some(Accidental::Flat) => write!(f, "{}♭", self.letter()),
+            None => write!(f, "{}", self.letter()),
+        }
+    }
+}
+
+/// Name of a MIDI note in scientific pitch notation, such as `C4` for the middle C (note 60).
+///
+/// Octaves start at C, and the lowest MIDI note is `C-1`.
+/// Accidentals are spelled as in [`Key`], so that note 61 is `C♯4` and note 63 is `E♭4`.
+pub fn pitch_name(note: u7) -> String {
+    let note = note.as_int();
+    let key = Key::ALL[usize::from(note % 12)];
+    format!("{}{}", key, i16::from(note / 12) - 1)
+}
+
+/// Same as [`Key::letter`].
+impl From<Key> for char {
+    fn from(key: Key) -> char {
+        key.letter()
+    }
+}
+
+/// Accidental of a [`Key`], as returned by [`Key::accidental`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Accidental {
+    /// ♯, raising the letter by a semitone.
+    Sharp,
+    /// ♭, lowering the letter by a semitone.
+    Flat,
+}
+
+/// Parse a key from its letter and optional accidental, such as `C`, `C#`, `Db` or `B♭`.
+///
+/// Enharmonic spellings are accepted and resolve to the matching variant, so that `Db` is
+/// [`Key::Cs`].
+impl FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chars = s.trim().chars();
+        let natural: i8 = match chars.next() {
+            Some('C') => 0,
+            Some('D') => 2,
+            Some('E') => 4,
+            Some('F') => 5,
+            Some('G') => 7,
+            Some('A') => 9,
+            Some('B') => 11,
+            _ => bail!(err_invalid!("invalid key letter")),
+        };
+        let offset = match chars.as_str() {
+            "" => 0,
+            "#" | "♯" => 1,
+            "b" | "♭" => -1,
+            _ => bail!(err_invalid!("invalid key accidental")),
+        };
+        Ok(Key::ALL[(natural + offset).rem_euclid(12) as usize])
+    }
+}
+
 impl TryFrom<u8> for Key {
     type Error = Error;
 
@@ -364,49 +1378,427 @@ impl TryFrom<u8> for Key {
 // Number of variants in the Chord enum
 const CHORD_SIZE: usize = 37;
 
+// Chords of the chord mute field, from the 5th bit of its first byte to the last bit.
+const CHORD_MUTE_ORDER: [Chord; CHORD_SIZE - 1] = [
+    // byte 0 (First nibble is 0x0)
+    Chord::SpecialPercussion,
+    Chord::SpecialAutostart,
+    Chord::OnePlusTwoPlus5,
+    Chord::Sus4,
+    // byte 1
+    Chord::OnePlusFive,
+    Chord::OnePlusEight,
+    Chord::SevenAug,
+    Chord::Maj7aug,
+    Chord::SevenS9,
+    Chord::SevenB13,
+    Chord::SevenB9,
+    Chord::Seven13,
+    // byte 2
+    Chord::SevenS11,
+    Chord::Seven9,
+    Chord::SevenB5,
+    Chord::SevenSus4,
+    Chord::Seven,
+    Chord::Dim7,
+    Chord::Dim,
+    Chord::MinMaj7_9,
+    // byte 3
+    Chord::MinMaj7,
+    Chord::Min7_11,
+    Chord::Min7_9,
+    Chord::Min9,
+    Chord::Min7b5,
+    Chord::Min7,
+    Chord::Min6,
+    Chord::Min,
+    // byte 4
+    Chord::Aug,
+    Chord::Maj6_9,
+    Chord::Maj7_9,
+    Chord::Maj9,
+    Chord::Maj7s11,
+    Chord::Maj7,
+    Chord::Maj6,
+    Chord::Maj,
+];
+
 /// Chords variants found in style files
-#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
-enum Chord {
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub enum Chord {
     // TODO find more sensible chord names
+    /// Major (C)
     Maj,
+    /// Major sixth (C6)
     Maj6,
+    /// Major seventh (CM7)
     Maj7,
+    /// Major seventh sharp eleventh (CM7#11)
     Maj7s11,
+    /// Major add ninth (Cadd9)
     Maj9,
+    /// Major seventh ninth (CM7(9))
     Maj7_9,
+    /// Major sixth ninth (C6(9))
     Maj6_9,
+    /// Augmented (Caug)
     Aug,
+    /// Minor (Cm)
     Min,
+    /// Minor sixth (Cm6)
     Min6,
+    /// Minor seventh (Cm7)
     Min7,
+    /// Minor seventh flat fifth (Cm7b5)
     Min7b5,
+    /// Minor add ninth (Cm(9))
     Min9,
+    /// Minor seventh ninth (Cm7(9))
     Min7_9,
+    /// Minor seventh eleventh (Cm7(11))
     Min7_11,
+    /// Minor major seventh (CmM7)
     MinMaj7,
+    /// Minor major seventh ninth (CmM7(9))
     MinMaj7_9,
+    /// Diminished (Cdim)
     Dim,
+    /// Diminished seventh (Cdim7)
     Dim7,
+    /// Seventh (C7)
     Seven,
+    /// Seventh suspended fourth (C7sus4)
     SevenSus4,
+    /// Seventh flat fifth (C7b5)
     SevenB5,
+    /// Seventh ninth (C7(9))
     Seven9,
+    /// Seventh sharp eleventh (C7(#11))
     SevenS11,
+    /// Seventh thirteenth (C7(13))
     Seven13,
+    /// Seventh flat ninth (C7(b9))
     SevenB9,
+    /// Seventh flat thirteenth (C7(b13))
     SevenB13,
+    /// Seventh sharp ninth (C7(#9))
     SevenS9,
+    /// Major seventh augmented (CM7aug)
     Maj7aug,
+    /// Seventh augmented (C7aug)
     SevenAug,
+    /// Root and octave (C1+8), a partial chord with no third nor fifth
     OnePlusEight,
+    /// Root and fifth (C1+5), a partial chord with no third, also known as a power chord
     OnePlusFive,
+    /// Suspended fourth (Csus4)
     Sus4,
+    /// Root, second and fifth (C1+2+5), a partial chord with no third
     OnePlusTwoPlus5,
+    /// Chord cancel: no chord is played
     Cancel,
+    /// Not a chord: drums autostart flag of the chord mute field
     SpecialAutostart,
+    /// Not a chord: unknown percussion flag of the chord mute field
     SpecialPercussion,
 }
 
+/// The third of a chord, as returned by [`Chord::third`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Third {
+    /// Major third, four semitones above the root.
+    Major,
+    /// Minor third, three semitones above the root.
+    Minor,
+}
+
+impl Chord {
+    /// All chords, in declaration order.
+    pub(crate) const ALL: [Chord; CHORD_SIZE] = [
+        Chord::Maj,
+        Chord::Maj6,
+        Chord::Maj7,
+        Chord::Maj7s11,
+        Chord::Maj9,
+        Chord::Maj7_9,
+        Chord::Maj6_9,
+        Chord::Aug,
+        Chord::Min,
+        Chord::Min6,
+        Chord::Min7,
+        Chord::Min7b5,
+        Chord::Min9,
+        Chord::Min7_9,
+        Chord::Min7_11,
+        Chord::MinMaj7,
+        Chord::MinMaj7_9,
+        Chord::Dim,
+        Chord::Dim7,
+        Chord::Seven,
+        Chord::SevenSus4,
+        Chord::SevenB5,
+        Chord::Seven9,
+        Chord::SevenS11,
+        Chord::Seven13,
+        Chord::SevenB9,
+        Chord::SevenB13,
+        Chord::SevenS9,
+        Chord::Maj7aug,
+        Chord::SevenAug,
+        Chord::OnePlusEight,
+        Chord::OnePlusFive,
+        Chord::Sus4,
+        Chord::OnePlusTwoPlus5,
+        Chord::Cancel,
+        Chord::SpecialAutostart,
+        Chord::SpecialPercussion,
+    ];
+}
+
+impl Chord {
+    /// The full name of this chord quality, such as "Minor seventh" for [`Chord::Min7`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chord::Maj => "Major",
+            Chord::Maj6 => "Major sixth",
+            Chord::Maj7 => "Major seventh",
+            Chord::Maj7s11 => "Major seventh sharp eleventh",
+            Chord::Maj9 => "Major add ninth",
+            Chord::Maj7_9 => "Major seventh ninth",
+            Chord::Maj6_9 => "Major sixth ninth",
+            Chord::Aug => "Augmented",
+            Chord::Min => "Minor",
+            Chord::Min6 => "Minor sixth",
+            Chord::Min7 => "Minor seventh",
+            Chord::Min7b5 => "Minor seventh flat five (half-diminished)",
+            Chord::Min9 => "Minor add ninth",
+            Chord::Min7_9 => "Minor seventh ninth",
+            Chord::Min7_11 => "Minor seventh eleventh",
+            Chord::MinMaj7 => "Minor major seventh",
+            Chord::MinMaj7_9 => "Minor major seventh ninth",
+            Chord::Dim => "Diminished",
+            Chord::Dim7 => "Diminished seventh",
+            Chord::Seven => "Dominant seventh",
+            Chord::SevenSus4 => "Seventh suspended fourth",
+            Chord::SevenB5 => "Seventh flat five",
+            Chord::Seven9 => "Seventh ninth",
+            Chord::SevenS11 => "Seventh sharp eleventh",
+            Chord::Seven13 => "Seventh thirteenth",
+            Chord::SevenB9 => "Seventh flat ninth",
+            Chord::SevenB13 => "Seventh flat thirteenth",
+            Chord::SevenS9 => "Seventh sharp ninth",
+            Chord::Maj7aug => "Major seventh augmented",
+            Chord::SevenAug => "Seventh augmented",
+            Chord::OnePlusEight => "Root and octave",
+            Chord::OnePlusFive => "Root and fifth (power chord)",
+            Chord::Sus4 => "Suspended fourth",
+            Chord::OnePlusTwoPlus5 => "Root, second and fifth",
+            Chord::Cancel => "Chord cancel",
+            Chord::SpecialAutostart => "Drums autostart flag",
+            Chord::SpecialPercussion => "Percussion flag",
+        }
+    }
+
+    /// The intervals making up this chord, in semitones above the root.
+    ///
+    /// Tensions are given above the octave, so that the ninth of [`Chord::Seven9`] is `14`.
+    /// [`Chord::Cancel`] and the special flags have no intervals.
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            Chord::Maj => &[0, 4, 7],
+            Chord::Maj6 => &[0, 4, 7, 9],
+            Chord::Maj7 => &[0, 4, 7, 11],
+            Chord::Maj7s11 => &[0, 4, 7, 11, 18],
+            Chord::Maj9 => &[0, 4, 7, 14],
+            Chord::Maj7_9 => &[0, 4, 7, 11, 14],
+            Chord::Maj6_9 => &[0, 4, 7, 9, 14],
+            Chord::Aug => &[0, 4, 8],
+            Chord::Min => &[0, 3, 7],
+            Chord::Min6 => &[0, 3, 7, 9],
+            Chord::Min7 => &[0, 3, 7, 10],
+            Chord::Min7b5 => &[0, 3, 6, 10],
+            Chord::Min9 => &[0, 3, 7, 14],
+            Chord::Min7_9 => &[0, 3, 7, 10, 14],
+            Chord::Min7_11 => &[0, 3, 7, 10, 17],
+            Chord::MinMaj7 => &[0, 3, 7, 11],
+            Chord::MinMaj7_9 => &[0, 3, 7, 11, 14],
+            Chord::Dim => &[0, 3, 6],
+            Chord::Dim7 => &[0, 3, 6, 9],
+            Chord::Seven => &[0, 4, 7, 10],
+            Chord::SevenSus4 => &[0, 5, 7, 10],
+            Chord::SevenB5 => &[0, 4, 6, 10],
+            Chord::Seven9 => &[0, 4, 7, 10, 14],
+            Chord::SevenS11 => &[0, 4, 7, 10, 18],
+            Chord::Seven13 => &[0, 4, 7, 10, 21],
+            Chord::SevenB9 => &[0, 4, 7, 10, 13],
+            Chord::SevenB13 => &[0, 4, 7, 10, 20],
+            Chord::SevenS9 => &[0, 4, 7, 10, 15],
+            Chord::Maj7aug => &[0, 4, 8, 11],
+            Chord::SevenAug => &[0, 4, 8, 10],
+            Chord::OnePlusEight => &[0, 12],
+            Chord::OnePlusFive => &[0, 7],
+            Chord::Sus4 => &[0, 5, 7],
+            Chord::OnePlusTwoPlus5 => &[0, 2, 7],
+            Chord::Cancel | Chord::SpecialAutostart | Chord::SpecialPercussion => &[],
+        }
+    }
+
+    /// The third of this chord, or `None` for chords without one such as [`Chord::Sus4`] and
+    /// [`Chord::OnePlusFive`].
+    ///
+    /// Only the third above the root is considered, so the sharp ninth of [`Chord::SevenS9`]
+    /// does not make it minor.
+    pub fn third(&self) -> Option<Third> {
+        self.intervals().iter().find_map(|interval| match interval {
+            3 => Some(Third::Minor),
+            4 => Some(Third::Major),
+            _ => None,
+        })
+    }
+
+    /// Whether this is one of the partial `1+x` chords, [`Chord::OnePlusEight`],
+    /// [`Chord::OnePlusFive`] and [`Chord::OnePlusTwoPlus5`].
+    ///
+    /// These are named after the intervals they are made of rather than a chord quality: they
+    /// have no third, and unlike [`Chord::Sus4`] nothing replaces it.
+    pub fn is_partial(&self) -> bool {
+        matches!(
+            self,
+            Chord::OnePlusEight | Chord::OnePlusFive | Chord::OnePlusTwoPlus5
+        )
+    }
+
+    /// A human readable description of this chord, for display to non-experts.
+    ///
+    /// The first line gives the full name and the intervals of the chord, and the second line
+    /// its symbol on a C root:
+    ///
+    /// ```text
+    /// Minor seventh flat five (half-diminished): root, ♭3, ♭5, ♭7
+    /// Symbol: Cm7♭5
+    /// ```
+    ///
+    /// [`Chord::Cancel`] and the special flags are described in a single line.
+    pub fn describe(&self) -> String {
+        let note = match self {
+            Chord::Cancel => Some("no chord is played"),
+            Chord::SpecialAutostart => Some("not a chord, reserved to drum tracks"),
+            Chord::SpecialPercussion => Some("not a chord, reserved to percussion tracks"),
+            _ => None,
+        };
+        if let Some(note) = note {
+            return format!("{}: {}", self.name(), note);
+        }
+        let intervals = self
+            .intervals()
+            .iter()
+            .map(|&interval| match (self, interval) {
+                // The diminished seventh is a doubly flat seventh, rather than a sixth
+                (Chord::Dim7, 9) => "𝄫7",
+                (_, 0) => "root",
+                (_, 2) => "2",
+                (_, 3) => "♭3",
+                (_, 4) => "3",
+                (_, 5) => "4",
+                (_, 6) => "♭5",
+                (_, 7) => "5",
+                (_, 8) => "♯5",
+                (_, 9) => "6",
+                (_, 10) => "♭7",
+                (_, 11) => "7",
+                (_, 12) => "8",
+                (_, 13) => "♭9",
+                (_, 14) => "9",
+                (_, 15) => "♯9",
+                (_, 17) => "11",
+                (_, 18) => "♯11",
+                (_, 20) => "♭13",
+                (_, 21) => "13",
+                _ => "?",
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{}: {}\nSymbol: C{}",
+            self.name(),
+            intervals.join(", "),
+            self.symbol_suffix()
+        )
+    }
+
+    /// The chord symbol, to be written after the root note, such as `m7` for a C minor seventh
+    /// written Cm7.
+    ///
+    /// The symbols are spelled as on Yamaha keyboards, so that major sevenths are written `M7`
+    /// and tensions are put in parentheses, as in `7(9)`. The major chord has an empty symbol, as
+    /// do [`Chord::Cancel`] and the special flags. See [`Chord::name`] for the full name.
+    pub fn symbol_suffix(&self) -> &'static str {
+        match self {
+            Chord::Maj => "",
+            Chord::Maj6 => "6",
+            Chord::Maj7 => "M7",
+            Chord::Maj7s11 => "M7♯11",
+            Chord::Maj9 => "add9",
+            Chord::Maj7_9 => "M7(9)",
+            Chord::Maj6_9 => "6(9)",
+            Chord::Aug => "aug",
+            Chord::Min => "m",
+            Chord::Min6 => "m6",
+            Chord::Min7 => "m7",
+            Chord::Min7b5 => "m7♭5",
+            Chord::Min9 => "m(9)",
+            Chord::Min7_9 => "m7(9)",
+            Chord::Min7_11 => "m7(11)",
+            Chord::MinMaj7 => "mM7",
+            Chord::MinMaj7_9 => "mM7(9)",
+            Chord::Dim => "dim",
+            Chord::Dim7 => "dim7",
+            Chord::Seven => "7",
+            Chord::SevenSus4 => "7sus4",
+            Chord::SevenB5 => "7♭5",
+            Chord::Seven9 => "7(9)",
+            Chord::SevenS11 => "7(♯11)",
+            Chord::Seven13 => "7(13)",
+            Chord::SevenB9 => "7(♭9)",
+            Chord::SevenB13 => "7(♭13)",
+            Chord::SevenS9 => "7(♯9)",
+            Chord::Maj7aug => "M7aug",
+            Chord::SevenAug => "7aug",
+            Chord::OnePlusEight => "1+8",
+            Chord::OnePlusFive => "1+5",
+            Chord::Sus4 => "sus4",
+            Chord::OnePlusTwoPlus5 => "1+2+5",
+            Chord::Cancel | Chord::SpecialAutostart | Chord::SpecialPercussion => "",
+        }
+    }
+
+    // Whether this is an actual chord type, rather than the chord cancel or a special flag of the
+    // chord mute field.
+    pub(crate) fn is_chord_type(self) -> bool {
+        !matches!(
+            self,
+            Chord::Cancel | Chord::SpecialAutostart | Chord::SpecialPercussion
+        )
+    }
+}
+
+/// Parse a chord from its symbol without the root, as shown by [`Chord::describe`], such as
+/// `m7♭5` for [`Chord::Min7b5`].
+///
+/// The ASCII `#` and `b` are accepted for `♯` and `♭`, and the empty string is the major chord.
+/// The chord cancel and the special flags have no symbol and cannot be parsed.
+impl FromStr for Chord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let symbol = s.trim().replace('#', "♯").replace('b', "♭");
+        Chord::ALL
+            .iter()
+            .copied()
+            .find(|chord| chord.is_chord_type() && chord.symbol_suffix() == symbol)
+            .ok_or_else(|| err_invalid!("unknown chord symbol").into())
+    }
+}
+
 impl TryFrom<u8> for Chord {
     type Error = Error;
 
@@ -453,16 +1845,56 @@ impl TryFrom<u8> for Chord {
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) enum RetriggerRule {
+/// What happens to the notes still sounding when the played chord changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RetriggerRule {
+    /// Notes are stopped.
     Stop,
+    /// Notes are pitch-shifted to match the new chord.
     PitchShift,
+    /// Notes are pitch-shifted to the root of the new chord.
     PitchShiftToRoot,
+    /// Notes are retriggered at the pitch matching the new chord.
     Retrigger,
+    /// Notes are retriggered at the root of the new chord.
     RetriggerToRoot,
+    /// Notes are generated from the chord rather than retriggered.
     NoteGenerator,
 }
 
+impl RetriggerRule {
+    /// A human readable description of what happens to the notes still sounding when the played
+    /// chord changes.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            RetriggerRule::Stop => "Notes are stopped",
+            RetriggerRule::PitchShift => {
+                "Notes keep sounding and are bent to their pitch in the new chord"
+            }
+            RetriggerRule::PitchShiftToRoot => {
+                "Notes keep sounding and are bent to the root of the new chord"
+            }
+            RetriggerRule::Retrigger => "Notes are played again at their pitch in the new chord",
+            RetriggerRule::RetriggerToRoot => "Notes are played again at the root of the new chord",
+            RetriggerRule::NoteGenerator => {
+                "Notes are generated from the new chord rather than from the sounding notes"
+            }
+        }
+    }
+}
+
+/// What to do with a sounding note when the played chord changes, as returned by
+/// [`Ctab::on_chord_change`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NoteAction {
+    /// Stop the note.
+    Stop,
+    /// Keep the note sounding, bent to the given pitch.
+    Shift(u7),
+    /// Stop the note and play the given pitch instead.
+    Retrigger(u7),
+}
+
 impl TryFrom<u8> for RetriggerRule {
     type Error = Error;
 
@@ -479,11 +1911,15 @@ impl TryFrom<u8> for RetriggerRule {
     }
 }
 
-#[derive(Debug, PartialEq, Default)]
-pub(crate) enum TranspositionType {
+/// Note Transposition Rule (NTR): how source notes follow the root of the played chord.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum TranspositionType {
+    /// Notes are transposed by the interval between the source and the played chord roots.
     #[default]
     RootTransposition,
+    /// Notes are kept as close as possible to the source notes.
     RootFixed,
+    /// Guitar specific transposition. SFFv2 only.
     Guitar,
 }
 
@@ -492,57 +1928,121 @@ impl TryFrom<(u8, Version)> for TranspositionType {
 
     fn try_from(value: (u8, Version)) -> Result<Self> {
         let (value, version) = value;
+        TranspositionType::read_with(value, version, &ParseOptions::default())
+    }
+}
+
+impl TranspositionType {
+    fn read_with(value: u8, version: Version, options: &ParseOptions) -> Result<Self> {
         Ok(match value {
             0x00 => Self::RootTransposition,
             0x01 => Self::RootFixed,
             0x02 => {
-                if version == Version::Ctab1 && cfg!(feature = "strict") {
-                    bail!(err_invalid!("Guitar transposition mode in SFFv1"));
+                if version == Version::Ctab1 {
+                    if options.strict() {
+                        bail!(&unsupported::SFF1_GUITAR_MODE);
+                    }
+                    options.skip(&unsupported::SFF1_GUITAR_MODE);
                 }
                 Self::Guitar
             }
             _ => {
-                if cfg!(feature = "strict") {
+                if options.strict() {
                     bail!(err_invalid!("unknown transposition mode"));
-                } else {
-                    // Return default transposition
-                    Self::default()
                 }
+                // Return default transposition
+                options.skip(err_invalid!("unknown transposition mode"));
+                Self::default()
             }
         })
     }
 }
 
-#[derive(Debug, PartialEq, Default)]
-pub(crate) enum TranspositionTable {
+/// Note Transposition Table (NTT): how source notes are adjusted to the type of the played chord.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum TranspositionTable {
+    /// Notes are not adjusted.
     #[default]
     Bypass,
+    /// Adjusted for melodic lines.
     Melody,
+    /// Adjusted for chord parts.
     Chord,
+    /// Melodic minor scale.
     MelodicMinor,
+    /// Harmonic minor scale.
     HarmonicMinor,
     // Only for `Version::Ctab2`
+    /// Melodic minor scale, on the fifth. SFFv2 only.
     MelodicMinor5th,
+    /// Harmonic minor scale, on the fifth. SFFv2 only.
     HarmonicMinor5th,
+    /// Natural minor scale. SFFv2 only.
     NaturalMinor,
+    /// Natural minor scale, on the fifth. SFFv2 only.
     NaturalMinor5th,
+    /// Dorian scale. SFFv2 only.
     Dorian,
+    /// Dorian scale, on the fifth. SFFv2 only.
     Dorian5th,
     // Only for `Version::Ctab1`
+    /// Bass line. SFFv1 only.
     Bass,
     // Only for NTR::Guitar (implies `Version::Ctab2`)
+    /// All purpose guitar table.
     AllPurpose,
+    /// Guitar strokes.
     Stroke,
+    /// Guitar arpeggios.
     Arpeggio,
 }
 
+impl TranspositionTable {
+    // Inverse of `TryFrom<(u8, Version)>`, `None` if the table is not valid in `version`.
+    fn encode(self, version: Version) -> Option<u8> {
+        Some(match (self, version) {
+            (Self::AllPurpose, _) => 0x00,
+            (Self::Stroke, _) => 0x01,
+            (Self::Arpeggio, _) => 0x02,
+            (Self::Bypass, _) => 0x00,
+            (Self::Melody, _) => 0x01,
+            (Self::Chord, _) => 0x02,
+            (Self::Bass, Version::Ctab1) => 0x03,
+            (Self::MelodicMinor, Version::Ctab1) => 0x04,
+            (Self::HarmonicMinor, Version::Ctab1) => 0x05,
+            (_, Version::Ctab1) | (Self::Bass, _) => return None,
+            (Self::MelodicMinor, _) => 0x03,
+            (Self::MelodicMinor5th, _) => 0x04,
+            (Self::HarmonicMinor, _) => 0x05,
+            (Self::HarmonicMinor5th, _) => 0x06,
+            (Self::NaturalMinor, _) => 0x07,
+            (Self::NaturalMinor5th, _) => 0x08,
+            (Self::Dorian, _) => 0x09,
+            (Self::Dorian5th, _) => 0x0A,
+        })
+    }
+}
+
 impl TryFrom<(u8, Version)> for TranspositionTable {
     type Error = Error;
 
     fn try_from(value: (u8, Version)) -> Result<Self> {
         let (value, version) = value;
+        TranspositionTable::read_with(value, version, &ParseOptions::default())
+    }
+}
+
+impl TranspositionTable {
+    fn read_with(value: u8, version: Version, options: &ParseOptions) -> Result<Self> {
         // ignore most significant bit (bass on)
         let value = value & 0b0111_1111;
+        // SFF2 only tables are still read outside of strict mode
+        if version == Version::Ctab1 && (0x06..=0x0A).contains(&value) {
+            if options.strict() {
+                bail!(&unsupported::SFF1_SFF2_TABLE);
+            }
+            options.skip(&unsupported::SFF1_SFF2_TABLE);
+        }
         Ok(match value {
             0x00 if version == Version::Guitar => Self::AllPurpose,
             0x00 => Self::Bypass,
@@ -555,40 +2055,316 @@ impl TryFrom<(u8, Version)> for TranspositionTable {
             0x04 if version == Version::Ctab1 => Self::MelodicMinor,
             0x04 => Self::MelodicMinor5th,
             0x05 => Self::HarmonicMinor,
-            _e if version == Version::Ctab1 && cfg!(feature = "strict") => {
-                bail!(err_invalid!("transposition table not valid in SFFv1"))
-            }
             0x06 => Self::HarmonicMinor5th,
             0x07 => Self::NaturalMinor,
             0x08 => Self::NaturalMinor5th,
             0x09 => Self::Dorian,
             0x0A => Self::Dorian5th,
             _e => {
-                if cfg!(feature = "strict") {
+                if options.strict() {
                     bail!(err_invalid!("unknown transposition table"));
-                } else {
-                    Self::default()
                 }
+                options.skip(err_invalid!("unknown transposition table"));
+                Self::default()
             }
         })
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) struct Table {
-    // Note Transposition Table
-    pub(crate) ntr: TranspositionType,
+/// A zone of source notes with its own transposition table, as used by [`Ctab::table_mut`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RangeSlot {
+    /// The notes below the mid zone. SFFv2 only.
+    Low,
+    /// The notes within [`Ctab::range_boundaries`]. SFFv2 only.
+    Mid,
+    /// The notes above the mid zone. SFFv2 only.
+    High,
+    /// Every note, the single zone of SFFv1 tables.
+    Full,
+}
+
+/// A note transposition table, describing how the notes of a source channel are transposed.
+///
+/// Tables compare equal when they transpose the same way: the raw bytes returned by
+/// [`Table::raw_ntr_byte`] and [`Table::raw_ntt_byte`] are not compared.
+#[derive(Clone, Debug)]
+pub struct Table {
     // Note Transposition Rule
-    pub(crate) ntt: TranspositionTable,
+    ntr: TranspositionType,
+    // Note Transposition Table
+    ntt: TranspositionTable,
     /// Whether bass mode is activated. Only relevant for [`Version::Ctab2`]
     bass_on: bool,
+    /// MSB of the NTT byte of a [`Version::Ctab1`] table, where it has no meaning.
+    v1_bass_flag: bool,
     /// Chords with a root higher than `high_key` are transposed to the octave below this limit.
-    pub(crate) high_key: Key,
+    high_key: Key,
     /// Notes outside these limits are transposed to the nearest octave within the range.
     /// [`note_range.0`] Note lower limit
     /// [`note_range.1`] Note higher limit
-    pub(crate) note_range: (u7, u7),
-    pub(crate) retrigger_rule: RetriggerRule,
+    note_range: (u7, u7),
+    /// MSB of the note range bytes, kept apart so that they are not read as part of the notes.
+    range_flags: (bool, bool),
+    retrigger_rule: RetriggerRule,
+    /// NTR and NTT bytes as read, before decoding.
+    raw_bytes: (u8, u8),
+}
+
+impl PartialEq for Table {
+    fn eq(&self, other: &Table) -> bool {
+        self.ntr == other.ntr
+            && self.ntt == other.ntt
+            && self.bass_on == other.bass_on
+            && self.v1_bass_flag == other.v1_bass_flag
+            && self.high_key == other.high_key
+            && self.note_range == other.note_range
+            && self.range_flags == other.range_flags
+            && self.retrigger_rule == other.retrigger_rule
+    }
+}
+
+impl Eq for Table {}
+
+impl Hash for Table {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ntr.hash(state);
+        self.ntt.hash(state);
+        self.bass_on.hash(state);
+        self.v1_bass_flag.hash(state);
+        self.high_key.hash(state);
+        self.note_range.hash(state);
+        self.range_flags.hash(state);
+        self.retrigger_rule.hash(state);
+    }
+}
+
+/// A table following the root of the played chord with no octave shift, as
+/// [`TranspositionType::RootTransposition`] with a [`Key::B`] high key, leaving the chord type
+/// aside with [`TranspositionTable::Bypass`].
+/// It covers the full note range, bass mode is off and notes are stopped on chord changes.
+impl Default for Table {
+    fn default() -> Self {
+        Table {
+            ntr: TranspositionType::RootTransposition,
+            ntt: TranspositionTable::Bypass,
+            bass_on: false,
+            v1_bass_flag: false,
+            high_key: Key::B,
+            note_range: (u7::new(0), u7::max_value()),
+            range_flags: (false, false),
+            retrigger_rule: RetriggerRule::Stop,
+            raw_bytes: (0x00, 0x00),
+        }
+    }
+}
+
+impl Table {
+    /// Note Transposition Rule.
+    pub fn ntr(&self) -> TranspositionType {
+        self.ntr
+    }
+
+    /// Note Transposition Table.
+    pub fn ntt(&self) -> TranspositionTable {
+        self.ntt
+    }
+
+    /// Chords with a root higher than this key are transposed to the octave below.
+    pub fn high_key(&self) -> Key {
+        self.high_key
+    }
+
+    /// Lower and higher note limits (inclusive).
+    ///
+    /// Notes outside these limits are transposed to the nearest octave within the range.
+    pub fn note_range(&self) -> (u7, u7) {
+        self.note_range
+    }
+
+    /// The most significant bit of the lower and higher note limit bytes.
+    ///
+    /// Note limits are 7-bit MIDI notes, so these bits are normally clear. No known encoding
+    /// sets them, but they are masked out of [`Table::note_range`] and kept here rather than
+    /// making the notes out of range, and written back as read.
+    pub fn range_flags(&self) -> (bool, bool) {
+        self.range_flags
+    }
+
+    /// Same as [`Table::note_range`], as plain MIDI note numbers.
+    ///
+    /// Use [`pitch_name`] to display them.
+    pub fn note_range_u8(&self) -> (u8, u8) {
+        (self.note_range.0.as_int(), self.note_range.1.as_int())
+    }
+
+    /// What happens to sounding notes when the played chord changes.
+    pub fn retrigger_rule(&self) -> RetriggerRule {
+        self.retrigger_rule
+    }
+
+    /// Change what happens to sounding notes when the played chord changes.
+    pub fn set_retrigger_rule(&mut self, rule: RetriggerRule) {
+        self.retrigger_rule = rule;
+    }
+
+    /// Whether bass mode is on, so that the notes follow the bass note of on-bass chords, such as
+    /// E for C/E, rather than their root.
+    ///
+    /// Only SFFv2 tables can set bass mode, it is always off for SFFv1 tables.
+    pub fn bass_on(&self) -> bool {
+        self.bass_on
+    }
+
+    /// Whether the bit holding bass mode in SFFv2, the MSB of the transposition table byte, is
+    /// set in a SFFv1 table.
+    ///
+    /// SFFv1 has no bass mode, and expresses bass lines with [`TranspositionTable::Bass`]
+    /// instead. Some SFFv1 files still set this bit: it is not honored as bass mode, but kept
+    /// here and written back as read. In strict mode it is an error instead, see
+    /// [`Error::unsupported_version`](crate::Error::unsupported_version).
+    /// It is dropped when the table is converted with [`Ctab::upgrade_to_v2`].
+    pub fn v1_bass_flag(&self) -> bool {
+        self.v1_bass_flag
+    }
+
+    /// The transposition rule byte as read, before decoding.
+    ///
+    /// Outside of strict mode, unknown values decode to the default
+    /// [`TranspositionType::RootTransposition`]: this keeps the original value. These are the
+    /// bytes as read, changes made to the table are not reflected here and they are not written
+    /// back.
+    pub fn raw_ntr_byte(&self) -> u8 {
+        self.raw_bytes.0
+    }
+
+    /// The transposition table byte as read, before decoding, including its MSB.
+    ///
+    /// Outside of strict mode, unknown values decode to the default
+    /// [`TranspositionTable::Bypass`]: this keeps the original value. As for
+    /// [`Table::raw_ntr_byte`], changes made to the table are not reflected here.
+    pub fn raw_ntt_byte(&self) -> u8 {
+        self.raw_bytes.1
+    }
+
+    fn encode(&self, version: Version) -> StdResult<[u8; TABLE_SIZE], &'static str> {
+        let mut ntt = self
+            .ntt
+            .encode(version)
+            .ok_or("transposition table not valid in this version")?;
+        let msb = match version {
+            Version::Ctab1 => self.v1_bass_flag,
+            _ => self.bass_on,
+        };
+        if msb {
+            ntt |= 0b1000_0000;
+        }
+        Ok([
+            self.ntr as u8,
+            ntt,
+            self.high_key as u8,
+            self.note_range.0.as_int() | (self.range_flags.0 as u8) << 7,
+            self.note_range.1.as_int() | (self.range_flags.1 as u8) << 7,
+            self.retrigger_rule as u8,
+        ])
+    }
+
+    /// Whether this table leaves the notes as recorded, apart from following the chord root.
+    ///
+    /// This is the case of a [`TranspositionTable::Bypass`] table with
+    /// [`TranspositionType::RootTransposition`] covering the full note range: notes are not
+    /// adjusted to the chord type, nor moved into a narrower range, and are only shifted by
+    /// [`Table::resolve_root`], which is no shift at all for chords on C.
+    /// A player can then apply that single shift to every note instead of calling
+    /// [`Table::transpose`] for each one, as long as the shifted notes stay within `0..=127`.
+    pub fn is_identity(&self) -> bool {
+        self.ntr == TranspositionType::RootTransposition
+            && self.ntt == TranspositionTable::Bypass
+            && self.note_range == (u7::new(0), u7::max_value())
+    }
+
+    /// Whether a chord with the given root is played an octave below because the root is
+    /// strictly higher than [`high_key`](Table::high_key).
+    ///
+    /// Keys are compared by their position in the chromatic scale starting from C.
+    /// The high key only applies to [`TranspositionType::RootTransposition`], so this is always
+    /// `false` for the other transposition types, see [`Table::resolve_root`].
+    pub fn wraps_root(&self, root: Key) -> bool {
+        self.ntr == TranspositionType::RootTransposition && root as u8 > self.high_key as u8
+    }
+
+    /// The shift in semitones applied to source notes when playing a chord with the given root,
+    /// for a source recorded in C.
+    ///
+    /// Keys are compared by their position in the chromatic scale starting from C, so that
+    /// [`Key::C`] is the lowest and [`Key::B`] the highest:
+    /// * with [`TranspositionType::RootTransposition`], the shift is the interval from C up to
+    ///   `chord_root`, minus an octave if `chord_root` is strictly higher than
+    ///   [`high_key`](Table::high_key). The result is in `-11..=11`.
+    /// * with [`TranspositionType::RootFixed`] and [`TranspositionType::Guitar`], the shift is
+    ///   the smallest interval from C to `chord_root`, going down an octave for roots strictly
+    ///   higher than F♯. The result is in `-5..=6`.
+    pub fn resolve_root(&self, chord_root: Key) -> i8 {
+        let root = chord_root as i8;
+        match self.ntr {
+            TranspositionType::RootTransposition if self.wraps_root(chord_root) => root - 12,
+            TranspositionType::RootTransposition => root,
+            TranspositionType::RootFixed | TranspositionType::Guitar if root > 6 => root - 12,
+            TranspositionType::RootFixed | TranspositionType::Guitar => root,
+        }
+    }
+
+    /// Transpose a source note to follow the root of the played chord.
+    ///
+    /// The source is assumed to be recorded in C and the played chord to be of the same type as
+    /// the source chord, so only the root transposition applies: the note is shifted as given
+    /// by [`Table::resolve_root`].
+    ///
+    /// The result is then moved by octaves to fit within [`note_range`](Table::note_range).
+    pub fn transpose(&self, note: u7, played_root: Key) -> u7 {
+        let shift = self.resolve_root(played_root);
+        self.clamp_note(note.as_int() as i16 + i16::from(shift))
+    }
+
+    // Same as `transpose`, for a source recorded in `source` rather than in C: the table follows
+    // the interval from the source to the played root, and its high key is relative to the source.
+    pub(crate) fn transpose_from(&self, note: u7, played_root: Key, source: Key) -> u7 {
+        let from_c = |key: Key| key.shifted(-(source as i8));
+        let table = Table {
+            high_key: from_c(self.high_key),
+            ..self.clone()
+        };
+        table.transpose(note, from_c(played_root))
+    }
+
+    /// Transpose every note in `notes`, yielding `(source, transposed)` pairs.
+    ///
+    /// See [`Table::transpose`] for the transposition rules.
+    pub fn map_range(&self, played_root: Key, notes: RangeInclusive<u7>) -> Vec<(u7, u7)> {
+        (notes.start().as_int()..=notes.end().as_int())
+            .map(u7::new)
+            .map(|note| (note, self.transpose(note, played_root)))
+            .collect()
+    }
+
+    // Move `note` by octaves until it fits in the note range.
+    // Ranges narrower than an octave cannot always fit the note, so it is clamped in that case.
+    fn clamp_note(&self, mut note: i16) -> u7 {
+        let (low, high) = (
+            self.note_range.0.as_int() as i16,
+            self.note_range.1.as_int() as i16,
+        );
+        if low > high {
+            return u7::new(note.clamp(0, 127) as u8);
+        }
+        while note < low {
+            note += 12;
+        }
+        while note > high {
+            note -= 12;
+        }
+        u7::new(note.clamp(low, high) as u8)
+    }
 }
 
 impl<'a> TryFrom<(&'a [u8], Version)> for Table {
@@ -596,25 +2372,57 @@ impl<'a> TryFrom<(&'a [u8], Version)> for Table {
 
     fn try_from(value: (&'a [u8], Version)) -> Result<Self> {
         let (value, version) = value;
-        if value.len() < TABLE_SIZE {
-            bail!(err_malformed!("data field too small"));
-        }
+        Table::read_with(value, version, &ParseOptions::default())
+    }
+}
+
+impl Table {
+    // The MSB of the NTT byte is bass mode in SFFv2, and is kept apart in SFFv1.
+    // An inverted note range is an error in strict mode, and is swapped otherwise.
+    // So is a note limit with its MSB set, which is kept apart as a flag otherwise.
+    fn read_with(mut value: &[u8], version: Version, options: &ParseOptions) -> Result<Table> {
+        let value: [u8; TABLE_SIZE] = value.take_array(err_malformed!("data field too small"))?;
 
-        let ntr = TranspositionType::try_from((value[0], version))?;
-        let ntt = TranspositionTable::try_from((value[1], version))?;
-        let bass_on = (value[1] & 0b1000_0000 != 0) && version == Version::Ctab2;
+        let ntr = TranspositionType::read_with(value[0], version, options)?;
+        let ntt = TranspositionTable::read_with(value[1], version, options)?;
+        let msb = value[1] & 0b1000_0000 != 0;
+        let bass_on = msb && version == Version::Ctab2;
+        let v1_bass_flag = msb && version == Version::Ctab1;
+        if v1_bass_flag {
+            if options.strict() {
+                bail!(&unsupported::SFF1_BASS_MODE);
+            }
+            options.skip(&unsupported::SFF1_BASS_MODE);
+        }
         let high_key = Key::try_from(value[2])?;
-        let note_range_low = u7::from(value[3]);
-        let note_range_high = u7::from(value[4]);
+        let range_flags = (value[3] & 0x80 != 0, value[4] & 0x80 != 0);
+        if range_flags.0 || range_flags.1 {
+            if options.strict() {
+                bail!(err_malformed!("note range MSB is set"));
+            }
+            options.skip(err_malformed!("note range MSB is set"));
+        }
+        let mut note_range_low = u7::from(value[3]);
+        let mut note_range_high = u7::from(value[4]);
+        if note_range_low > note_range_high {
+            if options.strict() {
+                bail!(err_malformed!("note range low limit above high limit"));
+            }
+            options.skip(err_malformed!("note range low limit above high limit"));
+            mem::swap(&mut note_range_low, &mut note_range_high);
+        }
         let retrigger_rule = RetriggerRule::try_from(value[5])?;
 
         Ok(Table {
             ntr,
             ntt,
             bass_on,
+            v1_bass_flag,
             high_key,
             note_range: (note_range_low, note_range_high),
+            range_flags,
             retrigger_rule,
+            raw_bytes: (value[0], value[1]),
         })
     }
 }
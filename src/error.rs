@@ -1,5 +1,8 @@
 use core::fmt;
 
+#[cfg(feature = "styles")]
+use crate::style::SffVersion;
+
 #[cfg(all(debug_assertions, feature = "alloc"))]
 mod error_impl {
     use super::{Error, ErrorExt, ErrorKind};
@@ -16,6 +19,11 @@ mod error_impl {
         fn kind(&self) -> ErrorKind {
             *self.inner.this
         }
+        #[cfg(feature = "styles")]
+        #[inline]
+        fn kind_ref(&self) -> &'static ErrorKind {
+            self.inner.this
+        }
         #[inline]
         fn source(&self) -> Option<&Error> {
             self.inner.src.as_ref()
@@ -56,6 +64,11 @@ mod error_impl {
         fn kind(&self) -> ErrorKind {
             *self.inner
         }
+        #[cfg(feature = "styles")]
+        #[inline]
+        fn kind_ref(&self) -> &'static ErrorKind {
+            self.inner
+        }
         #[inline]
         fn source(&self) -> Option<&Error> {
             None
@@ -112,6 +125,32 @@ impl Error {
     pub fn source(&self) -> Option<&Error> {
         ErrorExt::source(self)
     }
+
+    /// If this error, or one of its causes, is a style file feature used in a version of the
+    /// format that does not support it, such as a guitar transposition table in a SFF1 style,
+    /// the version of the style data holding the feature, and the feature.
+    ///
+    /// Like `Malformed` errors, these are only raised if the `strict` crate feature is enabled,
+    /// otherwise the feature is read anyway. They are told apart so that tools can offer to
+    /// upgrade the style instead.
+    ///
+    /// As error chains are not tracked in release mode, only the outermost error is looked at
+    /// there.
+    #[cfg(feature = "styles")]
+    pub fn unsupported_version(&self) -> Option<(SffVersion, &'static str)> {
+        let mut maybe_err = Some(self);
+        while let Some(err) = maybe_err {
+            let kind = err.kind_ref();
+            if let Some(&(found, _, context)) = unsupported::ALL
+                .iter()
+                .find(|(_, known, _)| core::ptr::eq(*known, kind))
+            {
+                return Some((found, context));
+            }
+            maybe_err = err.source();
+        }
+        None
+    }
 }
 impl fmt::Display for Error {
     #[inline]
@@ -142,6 +181,8 @@ impl std::error::Error for Error {
 
 trait ErrorExt {
     fn kind(&self) -> ErrorKind;
+    #[cfg(feature = "styles")]
+    fn kind_ref(&self) -> &'static ErrorKind;
     fn source(&self) -> Option<&Error>;
     fn chain_ctx(self, ctx: &'static ErrorKind) -> Error;
 }
@@ -202,6 +243,31 @@ macro_rules! err_malformed {
         ERR_KIND
     }};
 }
+/// Style file features used in a version of the format that does not support them.
+///
+/// These are `Invalid` errors, told apart by their address through
+/// [`Error::unsupported_version`], which is why they are statics.
+#[cfg(feature = "styles")]
+pub(crate) mod unsupported {
+    use super::ErrorKind;
+    use crate::style::SffVersion;
+
+    pub static SFF1_GUITAR_MODE: ErrorKind =
+        ErrorKind::Invalid("Guitar transposition mode in SFF1 style");
+    pub static SFF1_SFF2_TABLE: ErrorKind =
+        ErrorKind::Invalid("SFF2 transposition table in SFF1 style");
+    pub static SFF1_BASS_MODE: ErrorKind = ErrorKind::Invalid("bass mode in SFF1 style");
+
+    pub(super) static ALL: [(SffVersion, &ErrorKind, &str); 3] = [
+        (
+            SffVersion::V1,
+            &SFF1_GUITAR_MODE,
+            "Guitar transposition mode",
+        ),
+        (SffVersion::V1, &SFF1_SFF2_TABLE, "SFF2 transposition table"),
+        (SffVersion::V1, &SFF1_BASS_MODE, "bass mode"),
+    ];
+}
 
 pub(crate) trait ResultExt<T> {
     fn context(self, ctx: &'static ErrorKind) -> StdResult<T, Error>;
@@ -91,7 +91,7 @@ impl<W: Write> Seek for NotSeekable<W> {
     }
 }
 
-impl<'a, W: Write> Write for &'a mut W {
+impl<W: Write> Write for &mut W {
     type Error = W::Error;
     type Seekable = W::Seekable;
     #[inline]
@@ -153,7 +153,7 @@ pub struct Cursor<'a> {
 impl<'a> Cursor<'a> {
     /// Create a new cursor located at the start of the given buffer.
     #[inline]
-    pub fn new(buffer: &mut [u8]) -> Cursor {
+    pub fn new(buffer: &mut [u8]) -> Cursor<'_> {
         Cursor {
             buf: buffer,
             cur: 0,
@@ -166,7 +166,7 @@ impl<'a> Cursor<'a> {
     ///
     /// Panics if `cursor > buffer.len()`.
     #[inline]
-    pub fn from_parts(buffer: &mut [u8], cursor: usize) -> Cursor {
+    pub fn from_parts(buffer: &mut [u8], cursor: usize) -> Cursor<'_> {
         assert!(
             cursor <= buffer.len(),
             "cursor beyond the end of the buffer"
@@ -292,7 +292,7 @@ pub enum CursorError {
     /// The input SMF was invalid.
     InvalidInput(&'static str),
 }
-impl<'a> Write for &'a mut [u8] {
+impl Write for &mut [u8] {
     type Error = CursorError;
     type Seekable = NotSeekable<Self>;
     #[inline]
@@ -303,7 +303,7 @@ impl<'a> Write for &'a mut [u8] {
             Err(CursorError::OutOfSpace)
         } else {
             self[..buf.len()].copy_from_slice(buf);
-            let slice = mem::replace(self, &mut []);
+            let slice = mem::take(self);
             *self = &mut slice[buf.len()..];
             Ok(())
         }
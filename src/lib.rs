@@ -155,6 +155,12 @@
 //!   All types that are unavailable when a feature is disabled are marked as such in their
 //!   documentation.
 //!
+//! - `styles` (enabled by default)
+//!
+//!   This feature enables parsing Yamaha style files (`.sty`, `.prs`, ...) through the
+//!   [`StyleFile`](struct.StyleFile.html) type and the [`style`](style/index.html) module.
+//!   It depends on the `std` feature.
+//!
 //! - `strict`
 //!
 //!   By default `midly` will attempt to plow through non-standard and even obviously corrupted
@@ -190,6 +196,8 @@ mod error;
 mod prelude {
     #[cfg(feature = "std")]
     pub(crate) use crate::io::IoWrap;
+    #[cfg(feature = "styles")]
+    pub(crate) use crate::primitive::ReadExt;
     pub(crate) use crate::{
         error::{ErrorKind, Result, ResultExt, StdResult},
         io::{Seek, Write, WriteCounter, WriteResult},
@@ -210,26 +218,30 @@ mod prelude {
 }
 
 mod arena;
+#[cfg(feature = "styles")]
 mod casm;
+#[cfg(feature = "styles")]
 mod ctab;
 mod event;
 pub mod io;
 pub mod live;
+#[cfg(feature = "styles")]
 mod mdb;
+#[cfg(feature = "styles")]
 mod mh;
+#[cfg(feature = "styles")]
 mod ots;
 mod primitive;
 mod riff;
 mod smf;
 pub mod stream;
-
 #[cfg(feature = "styles")]
-pub use crate::smf::parse_style;
+pub mod style;
+
 #[cfg(feature = "std")]
 pub use crate::smf::write_std;
 #[cfg(feature = "styles")]
-#[cfg(feature = "alloc")]
-pub use crate::smf::Sff;
+pub use crate::style::{detect_sff_version, parse_style, Sff, StyleFile};
 #[cfg(feature = "alloc")]
 pub use crate::{
     arena::Arena,
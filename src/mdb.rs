@@ -1,12 +1,23 @@
 use crate::prelude::*;
-use crate::smf::{Chunk, ChunkIter};
+use crate::smf::{write_chunk, Chunk, ChunkIter};
+use crate::style::ParseOptions;
+use core::iter::FusedIterator;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 
+/// The Music Finder (MDB) section of a style file.
+///
+/// It holds the records of the songs this style is suited for.
 #[derive(Debug, Clone)]
-pub struct Mdb<'a>(pub(crate) RecordIter<'a>);
+pub struct Mdb {
+    records: Vec<Record>,
+    // The content of the FNRc chunk, if retained.
+    raw: Option<Vec<u8>>,
+}
 
-impl<'a> Mdb<'a> {
+impl Mdb {
     // get the first MDB section from a ChunkIter, additional ones are ignored.
-    pub(crate) fn parse(chunk_iter: ChunkIter<'a>) -> Result<Option<Mdb>> {
+    pub(crate) fn parse(chunk_iter: ChunkIter, options: &ParseOptions) -> Result<Option<Mdb>> {
         let mut mdb_iter = chunk_iter.filter(|c| matches!(c, Ok(Chunk::Mdb(..))));
         let mdb = match mdb_iter.next() {
             Some(maybe_chunk) => match maybe_chunk.context(err_invalid!("invalid MDB header"))? {
@@ -15,14 +26,114 @@ impl<'a> Mdb<'a> {
             },
             None => return Ok(None),
         }?;
-        let inner = ChunkIter::new(mdb);
-        Ok(Some(Mdb(RecordIter { inner })))
+        let mut record_iter = RecordIter::new(mdb);
+        let records =
+            core::iter::from_fn(|| record_iter.next_with(options)).collect::<Result<Vec<_>>>()?;
+        let raw = Some(mdb.to_vec()).filter(|_| options.retain_raw);
+        Ok(Some(Mdb { records, raw }))
+    }
+
+    // Get the MDB sections following the first one from a ChunkIter.
+    // A malformed section is an error in strict mode, and is otherwise skipped.
+    pub(crate) fn parse_rest(chunk_iter: ChunkIter, options: &ParseOptions) -> Result<Vec<Mdb>> {
+        let sections = chunk_iter.filter_map(|c| match c {
+            Ok(Chunk::Mdb(data)) => Some(data),
+            _ => None,
+        });
+        let mut mdbs = Vec::new();
+        for data in sections.skip(1) {
+            let mut record_iter = RecordIter::new(data);
+            match core::iter::from_fn(|| record_iter.next_with(options)).collect::<Result<Vec<_>>>()
+            {
+                Ok(records) => {
+                    let raw = Some(data.to_vec()).filter(|_| options.retain_raw);
+                    mdbs.push(Mdb { records, raw });
+                }
+                Err(err) if options.strict() => return Err(err),
+                Err(err) => options.skip(err),
+            }
+        }
+        Ok(mdbs)
+    }
+
+    // A section holding the given records.
+    pub(crate) fn new(records: Vec<Record>) -> Mdb {
+        Mdb { records, raw: None }
+    }
+
+    /// The song records of this section, in file order.
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// The content of the FNRc chunk this section was parsed from, if it was parsed with
+    /// [`ParseOptions::retain_raw`].
+    ///
+    /// These are the bytes as read: changes made to the records are not reflected here.
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    /// Whether this section holds no song record.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Mutable access to the song records of this section.
+    pub fn records_mut(&mut self) -> &mut [Record] {
+        &mut self.records
+    }
+
+    /// Write this section as a `FNRc` chunk, with each record written by [`Record::write`].
+    pub fn write<W: Write>(&self, out: &mut W) -> WriteResult<W> {
+        let mut data = Vec::new();
+        for record in &self.records {
+            record.write(&mut data).map_err(W::invalid_input)?;
+        }
+        write_chunk(b"FNRc", &data, out)
+    }
+
+    /// How many times each keyword appears across the records, from both keywords of each record.
+    ///
+    /// Keywords are trimmed and lowercased, so that ` Jazz` and `jazz` are counted together.
+    /// Empty keywords are not counted.
+    pub fn keyword_frequencies(&self) -> BTreeMap<String, usize> {
+        let mut frequencies = BTreeMap::new();
+        let keywords = self
+            .records
+            .iter()
+            .flat_map(|record| record.keyword1().into_iter().chain(record.keyword2()));
+        for keyword in keywords {
+            let keyword = keyword.trim().to_lowercase();
+            if !keyword.is_empty() {
+                *frequencies.entry(keyword).or_insert(0) += 1;
+            }
+        }
+        frequencies
+    }
+
+    /// The slowest and fastest tempos of the records, in beats per minute.
+    ///
+    /// Records without a valid tempo are skipped, and `None` is returned if no record is left.
+    pub fn tempo_range(&self) -> Option<(f64, f64)> {
+        self.records
+            .iter()
+            .filter_map(Record::bpm)
+            .fold(None, |range, bpm| match range {
+                None => Some((bpm, bpm)),
+                Some((min, max)) => Some((bpm.min(min), bpm.max(max))),
+            })
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct Record {
-    /// Tempo of the tune in ms / quarter-note
+/// A Music Finder record, describing a song this style is suited for.
+///
+/// Each text (title, genre and keywords) is stored in its own sub-chunk. When a record holds the
+/// same sub-chunk twice, the first one is kept and the following ones are ignored, or rejected
+/// in strict mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Tempo of the tune in µs / quarter-note
     tempo: u24,
     /// Time signature
     signature: Signature,
@@ -38,61 +149,250 @@ pub(crate) struct Record {
     /// Keyword associated with the song
     // chunk: Id::Keyword2
     keyword2: Option<String>,
+    /// Encoding of the text chunks in the file
+    encoding: Encoding,
 }
 
 impl Record {
-    fn read(chunk: Chunk) -> Result<Record> {
+    /// A record with the given tempo, in microseconds per quarter-note, and time signature.
+    ///
+    /// The title and genre are empty, and there are no keywords. Texts are written as UTF-8.
+    pub fn new(tempo: u24, upper: u8, lower: u8) -> Record {
+        Record {
+            tempo,
+            signature: Signature { upper, lower },
+            title: String::new(),
+            genre: String::new(),
+            keyword1: None,
+            keyword2: None,
+            encoding: Encoding::Utf8,
+        }
+    }
+
+    /// Tempo of the tune in microseconds per quarter-note.
+    pub fn tempo(&self) -> u24 {
+        self.tempo
+    }
+
+    /// Tempo of the tune in beats (quarter-notes) per minute.
+    ///
+    /// Returns `None` if the tempo is `0`.
+    pub fn bpm(&self) -> Option<f64> {
+        match self.tempo.as_int() {
+            0 => None,
+            tempo => Some(60_000_000.0 / f64::from(tempo)),
+        }
+    }
+
+    /// Convert a tempo in beats (quarter-notes) per minute to microseconds per quarter-note, as
+    /// stored in a record.
+    ///
+    /// Returns `None` if `bpm` is not a strictly positive number, or if the tempo does not fit in
+    /// 24 bits (below about 3.6 bpm).
+    pub fn tempo_from_bpm(bpm: f64) -> Option<u24> {
+        let tempo = (60_000_000.0 / bpm).round();
+        if (1.0..=f64::from(u24::max_value().as_int())).contains(&tempo) {
+            Some(u24::new(tempo as u32))
+        } else {
+            None
+        }
+    }
+
+    /// Time signature of the tune.
+    pub fn signature(&self) -> Signature {
+        self.signature
+    }
+
+    /// Title of the song.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Change the title of the song.
+    ///
+    /// Fails if the title contains characters that the [encoding](Record::encoding) of the
+    /// record cannot represent, or if it is too long to be written.
+    pub fn set_title(&mut self, title: &str) -> Result<()> {
+        let encoded = self
+            .encoding
+            .encode(title)
+            .ok_or(err_invalid!("title does not fit the record encoding"))?;
+        ensure!(
+            u32::try_from(encoded.len()).is_ok(),
+            err_invalid!("title is too long")
+        );
+        self.title = title.to_string();
+        Ok(())
+    }
+
+    /// A copy of this record with another title, see [`Record::set_title`].
+    pub fn with_title(&self, title: &str) -> Result<Record> {
+        let mut record = self.clone();
+        record.set_title(title)?;
+        Ok(record)
+    }
+
+    /// A copy of this record with another genre.
+    ///
+    /// Fails if the genre contains characters that the [encoding](Record::encoding) of the
+    /// record cannot represent, or if it is too long to be written.
+    pub fn with_genre(&self, genre: &str) -> Result<Record> {
+        ensure!(
+            self.fits(genre),
+            err_invalid!("genre does not fit the record encoding")
+        );
+        Ok(Record {
+            genre: genre.to_string(),
+            ..self.clone()
+        })
+    }
+
+    /// A copy of this record with other keywords, `None` removing a keyword.
+    ///
+    /// Fails if a keyword contains characters that the [encoding](Record::encoding) of the
+    /// record cannot represent, or if it is too long to be written.
+    pub fn with_keywords(&self, keyword1: Option<&str>, keyword2: Option<&str>) -> Result<Record> {
+        ensure!(
+            keyword1.into_iter().chain(keyword2).all(|k| self.fits(k)),
+            err_invalid!("keyword does not fit the record encoding")
+        );
+        Ok(Record {
+            keyword1: keyword1.map(str::to_string),
+            keyword2: keyword2.map(str::to_string),
+            ..self.clone()
+        })
+    }
+
+    // Whether a text field can be written with the encoding of this record.
+    fn fits(&self, text: &str) -> bool {
+        match self.encoding.encode(text) {
+            Some(encoded) => u32::try_from(encoded.len()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Genre of the song.
+    pub fn genre(&self) -> &str {
+        &self.genre
+    }
+
+    /// First keyword associated with the song, if any.
+    pub fn keyword1(&self) -> Option<&str> {
+        self.keyword1.as_deref()
+    }
+
+    /// Second keyword associated with the song, if any.
+    pub fn keyword2(&self) -> Option<&str> {
+        self.keyword2.as_deref()
+    }
+
+    /// Encoding the text fields of this record were stored with.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Write this record as a `FNRP` chunk, storing its text fields with the encoding they were
+    /// read with.
+    ///
+    /// The text sub-chunks are written in the canonical order: song title, genre, first keyword
+    /// and second keyword. This may differ from the order of the file the record was read from.
+    /// The title and genre are always written, the keywords only if present.
+    pub fn write<W: Write>(&self, out: &mut W) -> WriteResult<W> {
+        self.write_with_encoding(self.encoding, out)
+    }
+
+    /// Write this record as a `FNRP` chunk, storing its text fields with the given encoding.
+    ///
+    /// The layout is the same as [`Record::write`].
+    /// Fails if a text field contains characters that the encoding cannot represent.
+    pub fn write_with_encoding<W: Write>(&self, enc: Encoding, out: &mut W) -> WriteResult<W> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&self.tempo.as_int().to_be_bytes()[1..]);
+        data.extend_from_slice(&[self.signature.upper, self.signature.lower]);
+        let texts = [
+            (b"Mnam", Some(self.title.as_str())),
+            (b"Gnam", Some(self.genre.as_str())),
+            (b"Kwd1", self.keyword1.as_deref()),
+            (b"Kwd2", self.keyword2.as_deref()),
+        ];
+        for (id, text) in texts.iter() {
+            if let Some(text) = text {
+                let bytes = enc
+                    .encode(text)
+                    .ok_or_else(|| W::invalid_input("text does not fit the output encoding"))?;
+                write_chunk(id, &bytes, &mut data).map_err(W::invalid_input)?;
+            }
+        }
+        write_chunk(b"FNRP", &data, out)
+    }
+
+    // Read with the default options.
+    #[cfg(test)]
+    pub(crate) fn read(chunk: Chunk) -> Result<Record> {
+        Record::read_with(chunk, &ParseOptions::default())
+    }
+
+    // Repeated text sub-chunks are reported to `options`, see the `Record` documentation.
+    pub(crate) fn read_with(chunk: Chunk, options: &ParseOptions) -> Result<Record> {
         let mut value = match chunk {
             Chunk::Record(v) => v,
             _ => bail!(err_invalid!("not a Record chunk")),
         };
 
         let tempo = u24::read(&mut value)?;
-        // Signature
-        let upper = u8::read(&mut value)?;
-        let lower = u8::read(&mut value)?;
+        let [upper, lower] = value.take_array(err_malformed!("missing time signature"))?;
 
         // The rest of the data is chunks
-        let chunk_iter = ChunkIter::new(value);
+        let chunk_iter = chunk.sub_chunks().unwrap_or_default();
         // Chunks should be in order Song Title, Genre Name, Keyword1, Keyword2
         // We'll just process the iterator and get values as they come to deal with
         // malformed files.
-        let mut title = String::default();
-        let mut genre = String::default();
-        let mut keyword1: Option<String> = None;
-        let mut keyword2: Option<String> = None;
+        let mut title = None;
+        let mut genre = None;
+        let mut keyword1 = None;
+        let mut keyword2 = None;
         for chunk in chunk_iter {
-            match chunk {
-                Ok(Chunk::SongTitleData(t)) => {
-                    title = match std::str::from_utf8(t) {
-                        Ok(val) => val.to_string(),
-                        Err(_) => String::default(),
-                    }
-                }
-                Ok(Chunk::GenreTitleData(t)) => {
-                    genre = match std::str::from_utf8(t) {
-                        Ok(val) => val.to_string(),
-                        Err(_) => String::default(),
-                    }
+            let (slot, text) = match chunk {
+                Ok(Chunk::SongTitleData(t)) => (&mut title, t),
+                Ok(Chunk::GenreTitleData(t)) => (&mut genre, t),
+                Ok(Chunk::Keyword1(t)) => (&mut keyword1, t),
+                Ok(Chunk::Keyword2(t)) => (&mut keyword2, t),
+                Err(_) => Err(err_malformed!("failed to read chunk"))?,
+                _ => continue,
+            };
+            match slot {
+                None => *slot = Some(text),
+                Some(_) if options.strict() => {
+                    bail!(err_malformed!("duplicate text chunk in record"))
                 }
-                Ok(Chunk::Keyword1(t)) => {
-                    keyword1 = match std::str::from_utf8(t) {
-                        Ok(val) if !val.is_empty() => Some(val.to_string()),
-                        Ok(_) => None,
-                        Err(_) => None,
-                    }
+                Some(_) => options.skip(err_malformed!("duplicate text chunk in record")),
+            }
+        }
+        let [title, genre, keyword1, keyword2] =
+            [title, genre, keyword1, keyword2].map(Option::unwrap_or_default);
+        // All text chunks of a record share an encoding: UTF-8 if they all decode as such,
+        // Shift-JIS otherwise.
+        let texts = [title, genre, keyword1, keyword2];
+        let encoding = if texts.iter().all(|t| std::str::from_utf8(t).is_ok()) {
+            Encoding::Utf8
+        } else {
+            Encoding::ShiftJis
+        };
+        let decode = |t: &[u8]| -> Result<String> {
+            match read_string(t, encoding, false) {
+                Some(text) => Ok(text),
+                None if options.strict() => {
+                    bail!(err_malformed!("text chunk is not valid Shift-JIS"))
                 }
-                Ok(Chunk::Keyword2(t)) => {
-                    keyword2 = match std::str::from_utf8(t) {
-                        Ok(val) if !val.is_empty() => Some(val.to_string()),
-                        Ok(_) => None,
-                        Err(_) => None,
-                    }
+                None => {
+                    options.skip(err_malformed!("text chunk is not valid Shift-JIS"));
+                    Ok(String::default())
                 }
-                Err(_) => Err(err_malformed!("failed to read chunk"))?,
-                _ => (),
             }
-        }
+        };
+        let (title, genre) = (decode(title)?, decode(genre)?);
+        let keyword1 = Some(decode(keyword1)?).filter(|k| !k.is_empty());
+        let keyword2 = Some(decode(keyword2)?).filter(|k| !k.is_empty());
         Ok(Record {
             tempo,
             signature: Signature { upper, lower },
@@ -100,48 +400,229 @@ impl Record {
             genre,
             keyword1,
             keyword2,
+            encoding,
         })
     }
 }
 
+/// Text encoding of the string fields of a [`Record`].
+///
+/// Files made for the western market use plain ASCII, which is valid UTF-8, while files made for
+/// the japanese market usually use Shift-JIS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// UTF-8, a superset of ASCII.
+    Utf8,
+    /// Shift-JIS, the usual encoding of japanese text.
+    ShiftJis,
+}
+
+/// Decode a text field of a style chunk, or `None` if it is not valid in `encoding`.
+///
+/// With `trim_padding`, the trailing spaces and NUL bytes filling fixed size fields are removed.
+pub(crate) fn read_string(raw: &[u8], encoding: Encoding, trim_padding: bool) -> Option<String> {
+    let mut text = encoding.decode(raw)?;
+    if trim_padding {
+        let len = text.trim_end_matches(&[' ', '\0'][..]).len();
+        text.truncate(len);
+    }
+    Some(text)
+}
+
+impl Encoding {
+    /// Decode text in this encoding, or `None` if it is not valid.
+    fn decode(self, raw: &[u8]) -> Option<String> {
+        match self {
+            Encoding::Utf8 => std::str::from_utf8(raw).ok().map(str::to_string),
+            Encoding::ShiftJis => encoding_rs::SHIFT_JIS
+                .decode_without_bom_handling_and_without_replacement(raw)
+                .map(Cow::into_owned),
+        }
+    }
+
+    /// Encode text in this encoding, or `None` if some characters cannot be represented.
+    fn encode(self, text: &str) -> Option<Cow<'_, [u8]>> {
+        match self {
+            Encoding::Utf8 => Some(Cow::Borrowed(text.as_bytes())),
+            Encoding::ShiftJis => match encoding_rs::SHIFT_JIS.encode(text) {
+                (_, _, true) => None,
+                (bytes, _, false) => Some(bytes),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct RecordIter<'a> {
     inner: ChunkIter<'a>,
 }
 
-impl<'a> Iterator for RecordIter<'a> {
-    type Item = Result<Record>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let chunk = self.inner.next()?;
-        match chunk {
-            Ok(c) if matches!(c, Chunk::Record(..)) => match Record::read(c) {
-                Ok(record) => Some(Ok(record)),
+impl<'a> RecordIter<'a> {
+    pub(crate) fn new(raw: &'a [u8]) -> RecordIter<'a> {
+        RecordIter {
+            inner: ChunkIter::new(raw),
+        }
+    }
+}
+
+impl RecordIter<'_> {
+    /// Same as `next`, but reporting the errors that are skipped outside of strict mode.
+    pub(crate) fn next_with(&mut self, options: &ParseOptions) -> Option<Result<Record>> {
+        let item = loop {
+            let chunk = self.inner.next()?;
+            break match chunk {
+                Ok(c) if matches!(c, Chunk::Record(..)) => match Record::read_with(c, options) {
+                    Ok(item) => Some(Ok(item)),
+                    Err(err) => {
+                        let err = Err(err).context(err_invalid!("invalid Record"));
+                        if options.strict() {
+                            Some(err)
+                        } else {
+                            // Drop this one and carry on with the next chunk
+                            options.skip_result(err);
+                            continue;
+                        }
+                    }
+                },
+                Ok(_) if options.strict() => Some(Err(err_invalid!(
+                    "found a chunk not belonging in an MDB section"
+                )
+                .into())),
+                // Stray chunks are dropped, the following ones are still read
+                Ok(_) => {
+                    options.skip(err_invalid!(
+                        "found a chunk not belonging in an MDB section"
+                    ));
+                    continue;
+                }
                 Err(err) => {
-                    if cfg!(feature = "strict") {
-                        Some(Err(err).context(err_invalid!("invalid Record")))
+                    // The chunk boundaries are lost, so nothing can be read after it
+                    let err = Err(err).context(err_malformed!("malformed Record"));
+                    if options.strict() {
+                        Some(err)
                     } else {
-                        None
+                        options.skip_result(err)
                     }
                 }
-            },
-            // Wrong chunk type
-            Ok(_) => None,
-            Err(err) => {
-                if cfg!(feature = "strict") {
-                    Some(Err(err).context(err_malformed!("malformed Record")))
-                } else {
-                    None
-                }
-            }
+            };
+        };
+        if !matches!(item, Some(Ok(_))) {
+            self.inner = ChunkIter::new(&[]);
         }
+        item
     }
 }
 
+/// Once an error is yielded or the iteration stops, the iterator is exhausted.
+impl<'a> Iterator for RecordIter<'a> {
+    type Item = Result<Record>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with(&ParseOptions::default())
+    }
+}
+
+impl FusedIterator for RecordIter<'_> {}
+
 /// Time signature as a fraction, like in normal musical notation
-#[derive(Debug, PartialEq)]
-pub(crate) struct Signature {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Signature {
     /// How many notes per bar
     upper: u8,
     /// note being counted
     lower: u8,
 }
+
+impl Signature {
+    /// Cut time, 2/2.
+    pub const TWO_TWO: Signature = Signature { upper: 2, lower: 2 };
+    /// 2/4.
+    pub const TWO_FOUR: Signature = Signature { upper: 2, lower: 4 };
+    /// 3/4.
+    pub const THREE_FOUR: Signature = Signature { upper: 3, lower: 4 };
+    /// Common time, 4/4.
+    pub const FOUR_FOUR: Signature = Signature { upper: 4, lower: 4 };
+    /// 5/4.
+    pub const FIVE_FOUR: Signature = Signature { upper: 5, lower: 4 };
+    /// 6/8.
+    pub const SIX_EIGHT: Signature = Signature { upper: 6, lower: 8 };
+    /// 7/8.
+    pub const SEVEN_EIGHT: Signature = Signature { upper: 7, lower: 8 };
+    /// 9/8.
+    pub const NINE_EIGHT: Signature = Signature { upper: 9, lower: 8 };
+    /// 12/8.
+    pub const TWELVE_EIGHT: Signature = Signature {
+        upper: 12,
+        lower: 8,
+    };
+
+    /// The well-known signatures above, in the order they are declared, such as offered by
+    /// signature pickers.
+    pub fn common() -> &'static [Signature] {
+        &[
+            Signature::TWO_TWO,
+            Signature::TWO_FOUR,
+            Signature::THREE_FOUR,
+            Signature::FOUR_FOUR,
+            Signature::FIVE_FOUR,
+            Signature::SIX_EIGHT,
+            Signature::SEVEN_EIGHT,
+            Signature::NINE_EIGHT,
+            Signature::TWELVE_EIGHT,
+        ]
+    }
+
+    /// How many notes per bar.
+    pub fn numerator(&self) -> u8 {
+        self.upper
+    }
+
+    /// The note value being counted, as in the lower number of the written signature.
+    pub fn denominator(&self) -> u8 {
+        self.lower
+    }
+
+    /// The class of meter of this signature, such as `Simple` for marches or `Compound` for
+    /// jigs.
+    ///
+    /// Since the denominator only sets the note value of a beat, the class depends on the
+    /// numerator:
+    /// * 1 to 4 beats, or any other even number of beats that is not a multiple of 3, make a
+    ///   simple meter (2/4, 3/4, 4/4, 8/4);
+    /// * multiples of 3 beats above 3 make a compound meter (6/8, 9/8, 12/8);
+    /// * any other odd number of beats make a complex meter (5/4, 7/8, 11/8).
+    pub fn meter_class(&self) -> MeterClass {
+        match self.upper {
+            0..=4 => MeterClass::Simple,
+            n if n % 3 == 0 => MeterClass::Compound,
+            n if n % 2 == 0 => MeterClass::Simple,
+            _ => MeterClass::Complex,
+        }
+    }
+}
+
+/// A signature from its `(numerator, denominator)` pair, such as `(3, 4)` for 3/4.
+///
+/// The pair is taken as is, as found in Music Finder records.
+impl From<(u8, u8)> for Signature {
+    fn from((upper, lower): (u8, u8)) -> Signature {
+        Signature { upper, lower }
+    }
+}
+
+/// The `(numerator, denominator)` pair of a signature.
+impl From<Signature> for (u8, u8) {
+    fn from(signature: Signature) -> (u8, u8) {
+        (signature.upper, signature.lower)
+    }
+}
+
+/// Class of meter of a time signature, as returned by [`Signature::meter_class`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MeterClass {
+    /// Beats divided in two, such as 2/4, 3/4 or 4/4.
+    Simple,
+    /// Beats divided in three, such as 6/8, 9/8 or 12/8.
+    Compound,
+    /// Irregular beats, such as 5/4 or 7/8.
+    Complex,
+}
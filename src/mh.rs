@@ -1,6 +1,12 @@
 use crate::prelude::*;
 use crate::smf::{Chunk, ChunkIter};
 
+/// The MH section of a style file, whose purpose is unknown.
+///
+/// No field of the MH content is documented, so none is decoded: in particular no default
+/// tempo or section length is known to be stored here. The content is available as is from
+/// [`Mh::raw`]. The tempo of a style is given by the tempo meta event of its first track, and
+/// the tempo of each Music Finder record by [`Record::tempo`](crate::style::Record::tempo).
 #[derive(Clone, Debug)]
 pub struct Mh<'a>(&'a [u8]);
 
@@ -17,4 +23,24 @@ impl<'a> Mh<'a> {
         }?;
         Ok(Some(Mh(mh)))
     }
+
+    // A section with the given chunk content.
+    pub(crate) fn new(raw: &'a [u8]) -> Mh<'a> {
+        Mh(raw)
+    }
+
+    /// The raw content of the MH chunk.
+    pub fn raw(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Length of the content of the MH chunk, in bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether the MH chunk has no content.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
@@ -1,8 +1,14 @@
-use crate::smf::{Chunk, ChunkIter};
-use crate::{prelude::*, TrackIter};
+use crate::casm::StylePart;
+use crate::smf::{Chunk, ChunkIter, EventIter};
+use crate::{prelude::*, MidiMessage, TrackEventKind, TrackIter};
 
+/// The One Touch Settings (OTS) section of a style file.
+///
+/// Each setting is stored as a MIDI track.
 #[derive(Debug, Clone)]
-pub struct Ots<'a>(pub TrackIter<'a>);
+pub struct Ots<'a> {
+    tracks: TrackIter<'a>,
+}
 
 impl<'a> Ots<'a> {
     // get the first OTS section from a ChunkIter, additional ones are ignored.
@@ -16,7 +22,193 @@ impl<'a> Ots<'a> {
             None => return Ok(None),
         }?;
 
-        let tracks = TrackIter::new(ots);
-        Ok(Some(Ots(tracks)))
+        Ok(Some(Ots::new(ots)))
+    }
+
+    // A section with the given chunk content.
+    pub(crate) fn new(raw: &'a [u8]) -> Ots<'a> {
+        Ots {
+            tracks: TrackIter::new(raw),
+        }
+    }
+
+    /// The raw content of the OTS chunk.
+    pub fn raw(&self) -> &'a [u8] {
+        self.tracks.unread()
+    }
+
+    /// The tracks of this section, one for each setting, without any interpretation.
+    pub fn tracks(&self) -> TrackIter<'a> {
+        self.tracks.clone()
+    }
+
+    /// Whether this section holds no setting track.
+    pub fn is_empty(&self) -> bool {
+        self.tracks().next().is_none()
+    }
+
+    /// The settings of this section, along with the style part each one is linked to.
+    ///
+    /// The OTS section does not encode any linkage, so it is assumed to be positional, as done
+    /// by the "OTS Link" function of the instruments: settings 1 to 4 are linked to
+    /// [`StylePart::MainA`] to [`StylePart::MainD`].
+    /// Any additional setting is not linked to a style part.
+    ///
+    /// Settings that cannot be read are skipped, without shifting the position of the following
+    /// ones, which stays the one used by [`Ots::effect_settings`].
+    pub fn settings(&self) -> Vec<(Option<StylePart>, OtsSetting<'a>)> {
+        const LINKED: [StylePart; 4] = [
+            StylePart::MainA,
+            StylePart::MainB,
+            StylePart::MainC,
+            StylePart::MainD,
+        ];
+        self.tracks()
+            .enumerate()
+            .filter_map(|(index, track)| Some((index, track.ok()?)))
+            .map(|(index, events)| (LINKED.get(index).copied(), OtsSetting { index, events }))
+            .collect()
+    }
+
+    /// The settings of this section, along with their conventional label, from "OTS 1" to
+    /// "OTS 4".
+    ///
+    /// Settings that cannot be read are skipped, leaving a gap in the labels of the others.
+    pub fn named_settings(&self) -> Vec<(String, OtsSetting<'a>)> {
+        self.settings()
+            .into_iter()
+            .map(|(_, setting)| (setting.name(), setting))
+            .collect()
+    }
+
+    /// The effect settings of the setting at `setting`, starting from `0`, in event order.
+    ///
+    /// See [`EffectSetting`] for the recognized events, any other event is ignored.
+    /// Fails if there is no such setting, or if its track cannot be read.
+    pub fn effect_settings(&self, setting: usize) -> Result<Vec<EffectSetting>> {
+        let events = match self.tracks().nth(setting) {
+            Some(events) => events?,
+            None => bail!(err_invalid!("no such OTS setting")),
+        };
+        let mut effects = Vec::new();
+        for event in events {
+            if let Some(effect) = EffectSetting::from_event(event?.kind) {
+                effects.push(effect);
+            }
+        }
+        Ok(effects)
+    }
+}
+
+/// An effect related event of a One Touch Setting, as returned by [`Ots::effect_settings`].
+///
+/// The send levels are read from control change events, and the effect types from XG effect
+/// parameter change system exclusive messages (`F0 43 1n 4C 02 01 aa mm ll F7`, where `aa` is
+/// the effect address and `mm ll` the type number).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EffectSetting {
+    /// Reverb send level of a channel, control change 91.
+    ReverbDepth {
+        /// Midi channel of the control change.
+        channel: u4,
+        /// Send level.
+        depth: u7,
+    },
+    /// Chorus send level of a channel, control change 93.
+    ChorusDepth {
+        /// Midi channel of the control change.
+        channel: u4,
+        /// Send level.
+        depth: u7,
+    },
+    /// Variation (DSP) send level of a channel, control change 94.
+    VariationDepth {
+        /// Midi channel of the control change.
+        channel: u4,
+        /// Send level.
+        depth: u7,
+    },
+    /// Reverb type, at XG effect address `00`.
+    ReverbType {
+        /// Type number MSB.
+        msb: u8,
+        /// Type number LSB.
+        lsb: u8,
+    },
+    /// Chorus type, at XG effect address `20`.
+    ChorusType {
+        /// Type number MSB.
+        msb: u8,
+        /// Type number LSB.
+        lsb: u8,
+    },
+    /// Variation (DSP) type, at XG effect address `40`.
+    VariationType {
+        /// Type number MSB.
+        msb: u8,
+        /// Type number LSB.
+        lsb: u8,
+    },
+}
+
+impl EffectSetting {
+    // The effect setting carried by an event, if any.
+    fn from_event(kind: TrackEventKind) -> Option<EffectSetting> {
+        match kind {
+            TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::Controller { controller, value },
+            } => match controller.as_int() {
+                91 => Some(EffectSetting::ReverbDepth {
+                    channel,
+                    depth: value,
+                }),
+                93 => Some(EffectSetting::ChorusDepth {
+                    channel,
+                    depth: value,
+                }),
+                94 => Some(EffectSetting::VariationDepth {
+                    channel,
+                    depth: value,
+                }),
+                _ => None,
+            },
+            // The leading 0xF0 is not part of the data
+            TrackEventKind::SysEx(&[0x43, device, 0x4C, 0x02, 0x01, address, msb, lsb, 0xF7])
+                if device & 0xF0 == 0x10 =>
+            {
+                match address {
+                    0x00 => Some(EffectSetting::ReverbType { msb, lsb }),
+                    0x20 => Some(EffectSetting::ChorusType { msb, lsb }),
+                    0x40 => Some(EffectSetting::VariationType { msb, lsb }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A single One Touch Setting, stored as a MIDI track.
+#[derive(Debug, Clone)]
+pub struct OtsSetting<'a> {
+    index: usize,
+    events: EventIter<'a>,
+}
+
+impl<'a> OtsSetting<'a> {
+    /// Position of this setting in the OTS section, starting from `0` for the first button.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The conventional label of this setting, such as "OTS 1" for the first button.
+    pub fn name(&self) -> String {
+        format!("OTS {}", self.index + 1)
+    }
+
+    /// The MIDI events making up this setting.
+    pub fn events(&self) -> EventIter<'a> {
+        self.events.clone()
     }
 }
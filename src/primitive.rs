@@ -3,6 +3,8 @@
 //! Also, primitives advance the file pointer when read.
 
 use crate::prelude::*;
+#[cfg(feature = "styles")]
+use crate::style::ParseOptions;
 
 pub(crate) trait SplitChecked: Sized {
     fn split_checked(&mut self, at: usize) -> Option<Self>;
@@ -20,6 +22,52 @@ impl<'a> SplitChecked for &'a [u8] {
     }
 }
 
+/// Checked reads of fixed size fields, failing with a consistent error on truncated data.
+#[cfg(feature = "styles")]
+pub(crate) trait ReadExt<'a>: Sized {
+    /// Split `n` bytes off the front, or fail with `what` if there are not enough of them.
+    fn take_checked(&mut self, n: usize, what: &'static ErrorKind) -> Result<&'a [u8]>;
+
+    /// Split an array of `N` bytes off the front, or fail with `what` if there are not enough.
+    fn take_array<const N: usize>(&mut self, what: &'static ErrorKind) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take_checked(N, what)?);
+        Ok(array)
+    }
+
+    /// Split `n` trailing bytes off the front, if there are enough of them.
+    ///
+    /// Missing trailing data is only an error in strict mode, see [`ParseOptions::strict`],
+    /// otherwise `None` is returned.
+    fn take_trailer(
+        &mut self,
+        n: usize,
+        what: &'static ErrorKind,
+        options: &ParseOptions,
+    ) -> Result<Option<&'a [u8]>>;
+}
+#[cfg(feature = "styles")]
+impl<'a> ReadExt<'a> for &'a [u8] {
+    #[inline]
+    fn take_checked(&mut self, n: usize, what: &'static ErrorKind) -> Result<&'a [u8]> {
+        Ok(self.split_checked(n).ok_or(what)?)
+    }
+
+    #[inline]
+    fn take_trailer(
+        &mut self,
+        n: usize,
+        what: &'static ErrorKind,
+        options: &ParseOptions,
+    ) -> Result<Option<&'a [u8]>> {
+        match self.split_checked(n) {
+            Some(trailer) => Ok(Some(trailer)),
+            None if options.strict() => Err(what.into()),
+            None => Ok(None),
+        }
+    }
+}
+
 /// Implemented on integer types for reading as big-endian.
 pub(crate) trait IntRead: Sized {
     /// Reads a big-endian integer.
@@ -450,7 +498,7 @@ pub(crate) fn read_varlen_slice<'a>(raw: &mut &'a [u8]) -> Result<&'a [u8]> {
             if cfg!(feature = "strict") {
                 bail!(err_malformed!("incomplete varlen slice"))
             } else {
-                mem::replace(raw, &[])
+                mem::take(raw)
             }
         }
     })
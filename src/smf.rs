@@ -1,11 +1,7 @@
 //! Specific to the SMF packaging of MIDI streams.
 
 use crate::{
-    casm::Casm,
     event::TrackEvent,
-    mdb::Mdb,
-    mh::Mh,
-    ots::Ots,
     prelude::*,
     primitive::{Format, Timing},
     riff,
@@ -82,7 +78,7 @@ impl<'a> Smf<'a> {
 
     /// Parse a `.mid` Standard Midi File from its raw bytes.
     /// If you casually want to parse `.mid` files, this is the function you're looking for.
-    pub fn parse(raw: &[u8]) -> Result<Smf> {
+    pub fn parse(raw: &[u8]) -> Result<Smf<'_>> {
         let (header, tracks) = parse(raw)?;
         let track_count_hint = tracks.track_count_hint;
         let tracks = tracks.collect_tracks()?;
@@ -190,7 +186,7 @@ impl<'a> SmfBytemap<'a> {
 
     /// Parse a Standard Midi File from its raw bytes, keeping a map to the original bytes that
     /// make up each event.
-    pub fn parse(raw: &[u8]) -> Result<SmfBytemap> {
+    pub fn parse(raw: &[u8]) -> Result<SmfBytemap<'_>> {
         let (header, tracks) = parse(raw)?;
         let track_count_hint = tracks.track_count_hint;
         let tracks = tracks.collect_bytemapped()?;
@@ -242,57 +238,12 @@ impl<'a> SmfBytemap<'a> {
     }
 }
 
-/// Represents a style file (SFF1 or SFF2) which is an extension of a SMF file with optional sections.
-///
-/// A SFF file contains:
-/// 1. a mandatory midi section (SMF);
-/// 2. an optional CASM section;
-/// 3. an optional One Touch Settings (OTS) section;
-/// 4. an optional Music Finder (MDB) section;
-/// 5. an optional MH section;
-/// This is the recommended order for the sections, but some files may present the optional sections in a
-/// different order.
-#[cfg(feature = "alloc")]
-#[cfg(feature = "styles")]
-#[derive(Clone, Debug)]
-pub struct Sff<'a> {
-    /// Header of the Midi section
-    pub header: Header,
-    /// Tracks of the Midi section
-    pub tracks: Vec<Track<'a>>,
-    /// The CASM section holds metadata related to the tracks in the Midi section
-    pub casm: Option<Casm<'a>>,
-    /// One Touch Settings section
-    pub ots: Option<Ots<'a>>,
-    /// Music Finder section
-    pub mdb: Option<Mdb<'a>>,
-    /// MH section with unknown purpose
-    pub mh: Option<Mh<'a>>,
-}
-
 #[cfg(feature = "alloc")]
-#[cfg(feature = "styles")]
-impl<'a> Sff<'a> {
-    /// Parse raw bytes and returns a Style structure if the parsing was successful
-    pub fn parse(raw: &'a [u8]) -> Result<Sff> {
-        let (header, tracks, casm, ots, mdb, mh) = parse_style(raw)?;
-        // Validate the Midi chunks
-        let track_count_hint = tracks.track_count_hint;
-        let tracks = tracks.collect_tracks()?;
-        validate_smf(&header, track_count_hint, tracks.len())?;
-        Ok(Sff {
-            header,
-            tracks,
-            casm,
-            ots,
-            mdb,
-            mh,
-        })
-    }
-}
-
-#[cfg(feature = "alloc")]
-fn validate_smf(header: &Header, track_count_hint: u16, track_count: usize) -> Result<()> {
+pub(crate) fn validate_smf(
+    header: &Header,
+    track_count_hint: u16,
+    track_count: usize,
+) -> Result<()> {
     if cfg!(feature = "strict") {
         ensure!(
             track_count_hint as usize == track_count,
@@ -312,7 +263,7 @@ fn validate_smf(header: &Header, track_count_hint: u16, track_count: usize) -> R
 /// The track iterator that is returned yields event iterators, which in turn yield concrete events.
 ///
 /// This function is always available, even in `no_std` environments.
-pub fn parse(raw: &[u8]) -> Result<(Header, TrackIter)> {
+pub fn parse(raw: &[u8]) -> Result<(Header, TrackIter<'_>)> {
     let raw = match raw.get(..4) {
         Some(b"RIFF") => riff::unwrap(raw)?,
         Some(b"MThd") => raw,
@@ -323,6 +274,7 @@ pub fn parse(raw: &[u8]) -> Result<(Header, TrackIter)> {
         Some(maybe_chunk) => match maybe_chunk.context(err_invalid!("invalid midi header"))? {
             Chunk::Header(header, track_count) => Ok((header, track_count)),
             Chunk::Track(_) => Err(err_invalid!("expected header, found track")),
+            #[cfg(feature = "styles")]
             _ => Err(err_invalid!("unknown header")),
         },
         None => Err(err_invalid!("no midi header chunk")),
@@ -331,50 +283,6 @@ pub fn parse(raw: &[u8]) -> Result<(Header, TrackIter)> {
     Ok((header, tracks))
 }
 
-/// Parse a raw STYLE file, yielding is SMF component and any optional data part.
-///
-/// The Midi data is parsed as a Header chunk and a track event iterator.
-/// All the Yamaha style chunks are provided as [`Option`].
-/// Oldest SFF1 style files are converted to the new SFF2 structure. This conversion is reversible.
-/// However SFF2 styles cannot be converted to SFF1 without loss of information.
-#[allow(clippy::type_complexity)]
-#[cfg(feature = "styles")]
-pub fn parse_style(
-    raw: &[u8],
-) -> Result<(
-    Header,
-    TrackIter,
-    Option<Casm>,
-    Option<Ots>,
-    Option<Mdb>,
-    Option<Mh>,
-)> {
-    let raw = match raw.get(..4) {
-        Some(b"MThd") => raw,
-        _ => bail!(err_invalid!("not a style file")),
-    };
-    let mut chunks = ChunkIter::new(raw);
-    // First chunks should be: 1) Midi header chunk, 2) Tracks chunk
-    let (header, track_count) = match chunks.next() {
-        Some(maybe_chunk) => match maybe_chunk.context(err_invalid!("invalid midi header"))? {
-            Chunk::Header(header, track_count) => Ok((header, track_count)),
-            _ => Err(err_invalid!(
-                "expected midi header, found another chunk type"
-            )),
-        },
-        None => Err(err_invalid!("no midi header chunk")),
-    }?;
-    // We need one iterator for each section of the style file.
-    // We are just cloning the pointer, so this operation should be cheap.
-    let casm = Casm::parse(chunks.clone())?;
-    let ots = Ots::parse(chunks.clone())?;
-    let mdb = Mdb::parse(chunks.clone())?;
-    let mh = Mh::parse(chunks.clone())?;
-    let tracks = chunks.as_tracks(track_count);
-
-    Ok((header, tracks, casm, ots, mdb, mh))
-}
-
 /// Encode and write a generic MIDI file into the given generic writer.
 /// The MIDI file is represented by a header and a list of tracks.
 ///
@@ -490,19 +398,46 @@ where
     write(header, tracks, &mut IoWrap(out))
 }
 
-#[derive(Clone, Debug)]
+/// Write a chunk with the given id and content.
+#[cfg(feature = "styles")]
+pub(crate) fn write_chunk<W: Write>(id: &[u8; 4], data: &[u8], out: &mut W) -> WriteResult<W> {
+    let len = u32::try_from(data.len())
+        .map_err(|_| W::invalid_input("chunk length exceeds 32 bit range"))?;
+    out.write(id)?;
+    out.write(&len.to_be_bytes())?;
+    out.write(data)?;
+    Ok(())
+}
+
+#[derive(Clone, Debug, Default)]
 pub(crate) struct ChunkIter<'a> {
     /// Starts at the current index, ends at EOF.
     raw: &'a [u8],
+    /// Whether a chunk was cut short by the end of the slice, or could not be read.
+    truncated: bool,
 }
 impl<'a> ChunkIter<'a> {
     #[inline]
-    pub(crate) fn new(raw: &'a [u8]) -> ChunkIter {
-        ChunkIter { raw }
+    pub(crate) fn new(raw: &'a [u8]) -> ChunkIter<'a> {
+        ChunkIter {
+            raw,
+            truncated: false,
+        }
+    }
+
+    /// Whether the iterator reached the end of its slice on a chunk boundary.
+    ///
+    /// This is `false` until the end is reached, and if the last chunk was cut short by the end
+    /// of the slice, which is only allowed outside of strict mode, or an error was found.
+    #[inline]
+    #[cfg(feature = "styles")]
+    pub(crate) fn ended_cleanly(&self) -> bool {
+        self.raw.is_empty() && !self.truncated
     }
 
     #[inline]
-    fn as_tracks(self, track_count_hint: u16) -> TrackIter<'a> {
+    #[allow(clippy::wrong_self_convention)]
+    pub(crate) fn as_tracks(self, track_count_hint: u16) -> TrackIter<'a> {
         TrackIter {
             chunks: self,
             track_count_hint,
@@ -514,7 +449,7 @@ impl<'a> Iterator for ChunkIter<'a> {
     #[inline]
     fn next(&mut self) -> Option<Result<Chunk<'a>>> {
         //Flip around option and result
-        match Chunk::read(&mut self.raw) {
+        match Chunk::read(&mut self.raw, &mut self.truncated) {
             Ok(Some(chunk)) => Some(Ok(chunk)),
             Ok(None) => None,
             Err(err) => {
@@ -522,28 +457,81 @@ impl<'a> Iterator for ChunkIter<'a> {
                 //This is to prevent use of corrupted state (such as reading a new Chunk from the
                 //middle of a malformed message)
                 self.raw = &[];
+                self.truncated = true;
                 Some(Err(err))
             }
         }
     }
 }
 
+/// A chunk as laid out in the file, before its id is looked at.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct RawChunk<'a> {
+    pub(crate) id: [u8; 4],
+    /// Length of the content, as declared in the chunk header.
+    pub(crate) declared: u32,
+    /// Content of the chunk, shorter than declared if the slice ends before the chunk does.
+    pub(crate) data: &'a [u8],
+}
+impl<'a> RawChunk<'a> {
+    /// Split the next chunk off the front of `raw`, or return `None` at the end of the slice.
+    ///
+    /// Fails if the 8 byte chunk header is cut short.
+    pub(crate) fn read(raw: &mut &'a [u8]) -> Result<Option<RawChunk<'a>>> {
+        if raw.is_empty() {
+            return Ok(None);
+        }
+        let mut id = [0; 4];
+        id.copy_from_slice(
+            raw.split_checked(4)
+                .ok_or(err_invalid!("failed to read chunkid"))?,
+        );
+        let declared = u32::read(raw).context(err_invalid!("failed to read chunklen"))?;
+        let data = match raw.split_checked(declared as usize) {
+            Some(data) => data,
+            None => mem::take(raw),
+        };
+        Ok(Some(RawChunk { id, declared, data }))
+    }
+
+    /// Whether the slice ended before the declared length of the chunk.
+    pub(crate) fn truncated(&self) -> bool {
+        self.data.len() != self.declared as usize
+    }
+
+    /// Amount of bytes this chunk took up in the slice it was read from.
+    ///
+    /// This is the 8 byte header followed by the content: chunks are not padded to an even
+    /// length, unlike RIFF chunks. A truncated chunk only counts the content actually present.
+    #[cfg(feature = "styles")]
+    pub(crate) fn total_len(&self) -> usize {
+        8 + self.data.len()
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub(crate) enum Chunk<'a> {
     Header(Header, u16),
     Track(&'a [u8]),
     /// Chunks found in the CASM section of a style file
     // b"CASM"
+    #[cfg(feature = "styles")]
     Casm(&'a [u8]),
     // b"CSEG"
+    #[cfg(feature = "styles")]
     Cseg(&'a [u8]),
     // b"Sdec"
+    #[cfg(feature = "styles")]
     Sdec(&'a [u8]),
     // b"Ctab"
+    #[cfg(feature = "styles")]
     Ctab1(&'a [u8]),
     // b"Ctb2"
+    #[cfg(feature = "styles")]
     Ctab2(&'a [u8]),
     // b"Cntt"
+    #[cfg(feature = "styles")]
+    #[allow(dead_code)]
     Cntt(&'a [u8]),
     /// Chunks found in an OTS section of a style file
     ///
@@ -551,52 +539,71 @@ pub(crate) enum Chunk<'a> {
     /// No empty OTS section should be written when creating a new style file.
     /// The OTS data section is a list of Tracks (with the same header as the Midi tracks).
     // b"OTSc"
+    #[cfg(feature = "styles")]
     Ots(&'a [u8]),
     /// Chunks found in the Music Finder section of a style file
     // b"FNRc"
+    #[cfg(feature = "styles")]
     Mdb(&'a [u8]),
     // b"FNRP"
+    #[cfg(feature = "styles")]
     Record(&'a [u8]),
     // b"Mnam"
+    #[cfg(feature = "styles")]
     SongTitleData(&'a [u8]),
     // b"Gnam"
+    #[cfg(feature = "styles")]
     GenreTitleData(&'a [u8]),
     // b"Kwd1"
+    #[cfg(feature = "styles")]
     Keyword1(&'a [u8]),
     // b"Kwd2"
+    #[cfg(feature = "styles")]
     Keyword2(&'a [u8]),
     /// Chunks found in the MH section of a style file
     // b"MHhd"
+    #[cfg(feature = "styles")]
     Mh(&'a [u8]),
     /// Track found in the MH section
     // b"MHtr"
+    #[cfg(feature = "styles")]
+    #[allow(dead_code)]
     MhTrack(&'a [u8]),
 }
 impl<'a> Chunk<'a> {
+    /// Iterate over the chunks nested in a container chunk (`CASM`, `CSEG`, `FNRc` and `FNRP`).
+    ///
+    /// The tempo and signature at the start of a `FNRP` record are skipped.
+    /// Returns `None` for leaf chunks.
+    #[cfg(feature = "styles")]
+    pub(crate) fn sub_chunks(&self) -> Option<ChunkIter<'a>> {
+        match *self {
+            Chunk::Casm(data) | Chunk::Cseg(data) | Chunk::Mdb(data) => Some(ChunkIter::new(data)),
+            Chunk::Record(data) => Some(ChunkIter::new(data.get(5..).unwrap_or(&[]))),
+            _ => None,
+        }
+    }
+
     /// Should be called with a byte slice at least as large as the chunk (ideally until EOF).
     /// The slice will be modified to point to the next chunk.
     /// If we're *exactly* at EOF (slice length 0), returns a None signalling no more chunks.
-    fn read(raw: &mut &'a [u8]) -> Result<Option<Chunk<'a>>> {
+    /// `truncated` is set if the chunk is cut short by the end of the slice.
+    fn read(raw: &mut &'a [u8], truncated: &mut bool) -> Result<Option<Chunk<'a>>> {
         Ok(loop {
-            if raw.is_empty() {
-                break None;
-            }
-            let id = raw
-                .split_checked(4)
-                .ok_or(err_invalid!("failed to read chunkid"))?;
-            let len = u32::read(raw).context(err_invalid!("failed to read chunklen"))?;
-            let chunkdata = match raw.split_checked(len as usize) {
-                Some(chunkdata) => chunkdata,
-                None => {
-                    if cfg!(feature = "strict") {
-                        bail!(err_malformed!("reached eof before chunk ended"));
-                    } else {
-                        //Just use the remainder of the file
-                        mem::take(raw)
-                    }
-                }
+            let chunk = match RawChunk::read(raw)? {
+                Some(chunk) => chunk,
+                None => break None,
             };
-            match id {
+            if chunk.truncated() {
+                if cfg!(feature = "strict") {
+                    bail!(err_malformed!("reached eof before chunk ended"));
+                } else {
+                    //Just use the remainder of the file
+                    *truncated = true;
+                }
+            }
+            let chunkdata = chunk.data;
+            match &chunk.id {
                 b"MThd" => {
                     let (header, track_count) = Header::read(chunkdata)?;
                     break Some(Chunk::Header(header, track_count));
@@ -604,48 +611,63 @@ impl<'a> Chunk<'a> {
                 b"MTrk" => {
                     break Some(Chunk::Track(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"CASM" => {
                     break Some(Chunk::Casm(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"CSEG" => {
                     break Some(Chunk::Cseg(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"Sdec" => {
                     break Some(Chunk::Sdec(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"Ctab" => {
                     break Some(Chunk::Ctab1(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"Ctb2" => {
                     break Some(Chunk::Ctab2(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"Cntt" => {
                     break Some(Chunk::Cntt(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"OTSc" => {
                     break Some(Chunk::Ots(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"FNRc" => {
                     break Some(Chunk::Mdb(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"FNRP" => {
                     break Some(Chunk::Record(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"Mnam" => {
                     break Some(Chunk::SongTitleData(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"Gnam" => {
                     break Some(Chunk::GenreTitleData(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"Kwd1" => {
                     break Some(Chunk::Keyword1(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"Kwd2" => {
                     break Some(Chunk::Keyword2(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"MHhd" => {
                     break Some(Chunk::Mh(chunkdata));
                 }
+                #[cfg(feature = "styles")]
                 b"MHtr" => {
                     break Some(Chunk::MhTrack(chunkdata));
                 }
@@ -753,20 +775,35 @@ impl<'a> fmt::Display for Chunk<'a> {
         let out = match self {
             Chunk::Header(..) => "Header",
             Chunk::Track(..) => "Track",
+            #[cfg(feature = "styles")]
             Chunk::Casm(..) => "Casm",
+            #[cfg(feature = "styles")]
             Chunk::Cseg(..) => "Cseg",
+            #[cfg(feature = "styles")]
             Chunk::Sdec(..) => "Sdec",
+            #[cfg(feature = "styles")]
             Chunk::Ctab1(..) => "Ctab1",
+            #[cfg(feature = "styles")]
             Chunk::Ctab2(..) => "Ctab2",
+            #[cfg(feature = "styles")]
             Chunk::Cntt(..) => "Cntt",
+            #[cfg(feature = "styles")]
             Chunk::Ots(..) => "Ots",
+            #[cfg(feature = "styles")]
             Chunk::Mdb(..) => "Mdb",
+            #[cfg(feature = "styles")]
             Chunk::Record(..) => "Record",
+            #[cfg(feature = "styles")]
             Chunk::SongTitleData(..) => "SongTitleData",
+            #[cfg(feature = "styles")]
             Chunk::GenreTitleData(..) => "GenreTitleData",
+            #[cfg(feature = "styles")]
             Chunk::Keyword1(..) => "Keyword1",
+            #[cfg(feature = "styles")]
             Chunk::Keyword2(..) => "Keyword2",
+            #[cfg(feature = "styles")]
             Chunk::Mh(..) => "Mh",
+            #[cfg(feature = "styles")]
             Chunk::MhTrack(..) => "MhTrack",
         };
         write!(f, "{} chunk", out)
@@ -799,7 +836,7 @@ impl Header {
         let timing = Timing::read(&mut raw)?;
         Ok((Header::new(format, timing), track_count))
     }
-    fn encode(&self, track_count: u16) -> [u8; 6] {
+    pub(crate) fn encode(&self, track_count: u16) -> [u8; 6] {
         let mut bytes = [0; 6];
         bytes[0..2].copy_from_slice(&self.format.encode()[..]);
         bytes[2..4].copy_from_slice(&track_count.to_be_bytes()[..]);
@@ -815,14 +852,14 @@ impl Header {
 #[derive(Clone, Debug)]
 pub struct TrackIter<'a> {
     chunks: ChunkIter<'a>,
-    track_count_hint: u16,
+    pub(crate) track_count_hint: u16,
 }
 impl<'a> TrackIter<'a> {
     /// Create an event iterator from raw SMF bytes, excluding the header.
     ///
     /// The main way to obtain raw SMF without a header is the [`unread`](#method.unread) method.
     #[inline]
-    pub fn new(raw: &[u8]) -> TrackIter {
+    pub fn new(raw: &[u8]) -> TrackIter<'_> {
         TrackIter {
             chunks: ChunkIter::new(raw),
             track_count_hint: 0,
@@ -903,6 +940,7 @@ impl<'a> Iterator for TrackIter<'a> {
                         }
                     }
                     // Other chunks. We are after the tracks, into the Style chunks.
+                    #[cfg(feature = "styles")]
                     Ok(..) => break None,
                     //Failed to read chunk
                     Err(err) => {
@@ -933,7 +971,7 @@ struct EventIterGeneric<'a, T> {
 }
 impl<'a, T: EventKind<'a>> EventIterGeneric<'a, T> {
     #[inline]
-    fn new(raw: &[u8]) -> EventIterGeneric<T> {
+    fn new(raw: &[u8]) -> EventIterGeneric<'_, T> {
         EventIterGeneric {
             raw,
             running_status: None,
@@ -1033,7 +1071,7 @@ impl<'a> EventIter<'a> {
     /// Usually these raw track bytes are obtained from the [`unread`](#method.unread) method on an
     /// event iterator.
     #[inline]
-    pub fn new(raw: &[u8]) -> EventIter {
+    pub fn new(raw: &[u8]) -> EventIter<'_> {
         EventIter {
             inner: EventIterGeneric::new(raw),
         }
@@ -1115,7 +1153,7 @@ impl<'a> EventBytemapIter<'a> {
     /// Usually these raw track bytes are obtained from the [`unread`](#method.unread) method on an
     /// event iterator.
     #[inline]
-    pub fn new(raw: &[u8]) -> EventBytemapIter {
+    pub fn new(raw: &[u8]) -> EventBytemapIter<'_> {
         EventBytemapIter {
             inner: EventIterGeneric::new(raw),
         }
@@ -135,6 +135,7 @@ impl<B: Buffer> MidiStream<B> {
 /// macro, and very rarely should be implemented manually.
 pub trait Buffer {
     /// Add a new slice of data to the tail of the buffer.
+    #[allow(clippy::result_unit_err)]
     fn push(&mut self, data: &[u7]) -> StdResult<(), ()>;
     /// Clear the entire buffer.
     fn clear(&mut self);
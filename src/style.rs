@@ -0,0 +1,1090 @@
+//! Yamaha style files (SFF1 and SFF2), an extension of Standard Midi Files.
+//!
+//! This module is only available with the `styles` feature enabled.
+
+use crate::{
+    ctab::Version,
+    io::WriteCounter,
+    num::u15,
+    prelude::*,
+    smf::{
+        validate_smf, write_chunk, Chunk, ChunkIter, EventIter, Header, RawChunk, Track, TrackIter,
+    },
+    Error, Format, MetaMessage, Timing, TrackEvent, TrackEventKind,
+};
+use core::cell::RefCell;
+use core::hash::{Hash, Hasher};
+use core::ops::Range;
+use std::collections::BTreeSet;
+
+pub use crate::{
+    casm::{Casm, Cseg, CsegWarning, OwnedCseg, StyleCategory, StylePart},
+    ctab::{
+        pitch_name, Accidental, AccompanimentPart, Chord, Ctab, Key, LostFeature, NoteAction,
+        OwnedCtab, RangeSlot, RetriggerRule, Table, Third, TranspositionTable, TranspositionType,
+    },
+    mdb::{Encoding, Mdb, MeterClass, Record, Signature},
+    mh::Mh,
+    ots::{EffectSetting, Ots, OtsSetting},
+};
+
+/// The most commonly needed style types, meant to be glob-imported.
+///
+/// ```rust
+/// use midly::style::prelude::*;
+///
+/// fn parse(bytes: &[u8]) -> Result<(), Error> {
+///     let style = StyleFile::parse(bytes)?;
+///     if let Some(casm) = &style.casm {
+///         for cseg in casm.csegs() {
+///             let parts: &[StylePart] = cseg.style_parts();
+///             let ctabs: &[Ctab] = cseg.ctabs();
+///             println!("{} parts, {} tables", parts.len(), ctabs.len());
+///         }
+///     }
+///     Ok(())
+/// }
+/// # parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+/// ```
+pub mod prelude {
+    pub use crate::{
+        style::{
+            Casm, Chord, Cseg, Ctab, Key, Mdb, Mh, Ots, Record, Signature, StyleFile,
+            StyleFileBuilder, StylePart, Table,
+        },
+        Error, ErrorKind,
+    };
+}
+
+/// Represents a style file (SFF1 or SFF2) which is an extension of a SMF file with optional sections.
+///
+/// A SFF file contains:
+/// 1. a mandatory midi section (SMF);
+/// 2. an optional CASM section;
+/// 3. an optional One Touch Settings (OTS) section;
+/// 4. an optional Music Finder (MDB) section;
+/// 5. an optional MH section;
+///
+/// This is the recommended order for the sections, but some files may present the optional sections in a
+/// different order. Such files are only rejected in strict mode.
+#[derive(Clone, Debug)]
+pub struct StyleFile<'a> {
+    /// Header of the Midi section
+    pub header: Header,
+    /// Tracks of the Midi section
+    pub tracks: Vec<Track<'a>>,
+    /// The CASM section holds metadata related to the tracks in the Midi section
+    pub casm: Option<Casm<'a>>,
+    /// One Touch Settings section
+    pub ots: Option<Ots<'a>>,
+    /// Music Finder section
+    ///
+    /// Only the first MDB section is kept here, see [`StyleFile::records`] for the others.
+    pub mdb: Option<Mdb>,
+    /// MH section with unknown purpose
+    pub mh: Option<Mh<'a>>,
+    // The MDB sections following the first one, which are rare.
+    more_mdbs: Vec<Mdb>,
+    // The raw bytes this style was parsed from.
+    raw: &'a [u8],
+    // Whether the style was edited since it was parsed, and `raw` is outdated.
+    dirty: bool,
+}
+
+/// Former name of [`StyleFile`].
+pub type Sff<'a> = StyleFile<'a>;
+
+/// Options to tune how a style file is parsed, for use with [`StyleFile::parse_with`].
+#[derive(Clone, Copy, Default)]
+pub struct ParseOptions<'h> {
+    /// Called whenever malformed data is skipped, or replaced by a default value.
+    ///
+    /// Outside of strict mode, the parser recovers from malformed style segments, accompaniment
+    /// tables and song records by dropping them. This callback receives the error that would
+    /// have been raised in strict mode, which helps finding out why some data is missing.
+    /// In strict mode these errors are returned instead, and the callback is never called,
+    /// unless [`lenient`](ParseOptions::lenient) is set.
+    pub on_skip: Option<&'h dyn Fn(&Error)>,
+    /// Alternate decoding of the chord type bytes of the accompaniment tables.
+    ///
+    /// Called with every source chord type byte before the standard `0x00..=0x22` table is
+    /// looked up: returning a chord overrides the standard decoding, while returning `None`
+    /// falls back to it.
+    /// This allows reading styles made by instruments that encode some chord types at other
+    /// byte values. No such table is built in, as the crate has no reference files for them.
+    pub chord_alias: Option<&'h dyn Fn(u8) -> Option<Chord>>,
+    /// Recover from malformed style segments, accompaniment tables and song records even when
+    /// the `strict` feature is enabled, as is done without it.
+    ///
+    /// This has no effect on the MIDI tracks, which are always read according to the `strict`
+    /// feature.
+    pub lenient: bool,
+    /// Keep the raw content of the decoded sections, available from [`Casm::raw`] and
+    /// [`Mdb::raw`].
+    ///
+    /// The CASM and MDB sections are decoded into their own structures, so their bytes are
+    /// dropped unless this is set. The OTS and MH sections are never decoded, and always give
+    /// their bytes through [`Ots::raw`] and [`Mh::raw`].
+    pub retain_raw: bool,
+}
+
+impl fmt::Debug for ParseOptions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ParseOptions")
+            .field("on_skip", &self.on_skip.map(|_| "Fn(&Error)"))
+            .field(
+                "chord_alias",
+                &self.chord_alias.map(|_| "Fn(u8) -> Option<Chord>"),
+            )
+            .field("lenient", &self.lenient)
+            .field("retain_raw", &self.retain_raw)
+            .finish()
+    }
+}
+
+impl ParseOptions<'_> {
+    // Whether malformed style data is an error, rather than skipped.
+    pub(crate) fn strict(&self) -> bool {
+        cfg!(feature = "strict") && !self.lenient
+    }
+
+    // Report an error skipped outside of strict mode.
+    pub(crate) fn skip(&self, err: impl Into<Error>) {
+        if let Some(on_skip) = self.on_skip {
+            on_skip(&err.into());
+        }
+    }
+
+    // Decode a chord type byte, honoring the chord aliases.
+    pub(crate) fn read_chord(&self, byte: u8) -> Result<Chord> {
+        match self.chord_alias.and_then(|alias| alias(byte)) {
+            Some(chord) => Ok(chord),
+            None => Chord::try_from(byte),
+        }
+    }
+
+    // Report the error of a skipped item, which yields no item.
+    pub(crate) fn skip_result<T>(&self, item: Result<T>) -> Option<Result<T>> {
+        if let Err(err) = item {
+            self.skip(err);
+        }
+        None
+    }
+}
+
+impl<'a> StyleFile<'a> {
+    /// Parse raw bytes and returns a Style structure if the parsing was successful
+    pub fn parse(raw: &'a [u8]) -> Result<StyleFile<'a>> {
+        StyleFile::parse_with(raw, ParseOptions::default())
+    }
+
+    /// Same as [`StyleFile::parse`], with the given options.
+    ///
+    /// ```rust
+    /// use midly::style::{ParseOptions, StyleFile};
+    /// use std::cell::Cell;
+    ///
+    /// let skipped = Cell::new(0);
+    /// let on_skip = |_: &midly::Error| skipped.set(skipped.get() + 1);
+    /// let options = ParseOptions {
+    ///     on_skip: Some(&on_skip),
+    ///     ..ParseOptions::default()
+    /// };
+    /// let style = StyleFile::parse_with(include_bytes!("../test-asset/sff2.prs"), options);
+    /// assert!(style.is_ok());
+    /// assert_eq!(skipped.get(), 0);
+    /// ```
+    pub fn parse_with(raw: &'a [u8], options: ParseOptions) -> Result<StyleFile<'a>> {
+        let (header, tracks, casm, ots, mdb, mh) = parse_sections(raw, &options)?;
+        let mut chunks = ChunkIter::new(raw);
+        chunks.by_ref().for_each(drop);
+        if !chunks.ended_cleanly() {
+            options.skip(err_malformed!("reached eof before chunk ended"));
+        }
+        // Validate the Midi chunks
+        let track_count_hint = tracks.track_count_hint;
+        let tracks = tracks.collect_tracks()?;
+        validate_smf(&header, track_count_hint, tracks.len())?;
+        Ok(StyleFile {
+            header,
+            tracks,
+            casm,
+            ots,
+            mdb,
+            mh,
+            more_mdbs: Mdb::parse_rest(ChunkIter::new(raw), &options)?,
+            raw,
+            dirty: false,
+        })
+    }
+
+    /// Parse as much of a style file as possible, along with everything that had to be skipped.
+    ///
+    /// Unlike [`StyleFile::parse`], malformed sections, style segments, accompaniment tables and
+    /// song records are dropped and reported instead of failing the whole parse, whether the
+    /// `strict` feature is enabled or not. A malformed MIDI track ends the track list.
+    /// An empty diagnostic list means nothing was lost.
+    ///
+    /// Only bytes that do not start with a MIDI header are an error, as there is nothing to
+    /// recover from them.
+    pub fn parse_lenient(raw: &'a [u8]) -> Result<(StyleFile<'a>, Vec<Diagnostic>)> {
+        let mut chunks = ChunkIter::new(check_magic(raw)?);
+        let (header, track_count) = read_header(&mut chunks)?;
+        let diagnostics = RefCell::new(Vec::new());
+        let report = |section: &[u8; 4], error: &Error| {
+            diagnostics.borrow_mut().push(Diagnostic {
+                section: *section,
+                error: error.clone(),
+            })
+        };
+        // Parse a section, reporting its errors under its chunk id
+        fn section<T>(
+            id: &[u8; 4],
+            report: &dyn Fn(&[u8; 4], &Error),
+            parse: impl FnOnce(&ParseOptions) -> Result<Option<T>>,
+        ) -> Option<T> {
+            let on_skip = |error: &Error| report(id, error);
+            let options = ParseOptions {
+                on_skip: Some(&on_skip),
+                lenient: true,
+                ..ParseOptions::default()
+            };
+            parse(&options).unwrap_or_else(|err| {
+                options.skip(err);
+                None
+            })
+        }
+        let casm = section(b"CASM", &report, |o| Casm::parse(chunks.clone(), o));
+        let ots = section(b"OTSc", &report, |_| Ots::parse(chunks.clone()));
+        let mdb = section(b"FNRc", &report, |o| Mdb::parse(chunks.clone(), o));
+        let more_mdbs = section(b"FNRc", &report, |o| {
+            Mdb::parse_rest(chunks.clone(), o).map(Some)
+        })
+        .unwrap_or_default();
+        let mh = section(b"MHhd", &report, |_| Mh::parse(chunks.clone()));
+
+        let mut tracks = Vec::new();
+        for track in chunks.as_tracks(track_count) {
+            match track.and_then(EventIter::into_vec) {
+                Ok(track) => tracks.push(track),
+                Err(err) => {
+                    report(b"MTrk", &err);
+                    break;
+                }
+            }
+        }
+        if let Err(err) = validate_smf(&header, track_count, tracks.len()) {
+            report(b"MTrk", &err);
+        }
+        let style = StyleFile {
+            header,
+            tracks,
+            casm,
+            ots,
+            mdb,
+            mh,
+            more_mdbs,
+            raw,
+            dirty: false,
+        };
+        Ok((style, diagnostics.into_inner()))
+    }
+
+    /// Parse only the header and metadata sections of a style file, skipping the MIDI tracks and
+    /// the CASM and OTS sections.
+    ///
+    /// Skipped chunks are stepped over by their declared length, without looking at their
+    /// content, which makes this function suited to indexing large collections of style files.
+    ///
+    /// The returned style has its `header`, `mdb` and `mh` fields populated as with
+    /// [`StyleFile::parse`], while `tracks` is empty and `casm` and `ots` are always `None`.
+    pub fn parse_metadata_only(raw: &'a [u8]) -> Result<StyleFile<'a>> {
+        let mut chunks = ChunkIter::new(check_magic(raw)?);
+        let (header, _) = read_header(&mut chunks)?;
+        Ok(StyleFile {
+            header,
+            tracks: Vec::new(),
+            casm: None,
+            ots: None,
+            mdb: Mdb::parse(chunks.clone(), &ParseOptions::default())?,
+            more_mdbs: Mdb::parse_rest(chunks.clone(), &ParseOptions::default())?,
+            mh: Mh::parse(chunks)?,
+            raw,
+            dirty: false,
+        })
+    }
+
+    /// Whether the style was edited through its editing methods, such as
+    /// [`rename`](StyleFile::rename), since it was parsed.
+    ///
+    /// A dirty style no longer matches the bytes it was parsed from, and must be serialized again
+    /// to keep the changes. Changes made directly to the public fields are not tracked.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Serialize the style again, with any change made to it.
+    ///
+    /// The sections are written in the recommended order: the MIDI header and tracks, then the
+    /// CASM, OTS, MDB and MH sections, and unknown chunks are dropped. Additional MDB sections,
+    /// which can be seen with [`StyleFile::records`], are written after the first one.
+    /// The MIDI tracks, the CASM section and the MDB section are encoded from their decoded form,
+    /// so that the changes made to them are kept. The OTS and MH sections are copied as they were
+    /// read.
+    ///
+    /// The following is not preserved, so that even an unchanged style may not be written back
+    /// byte for byte:
+    /// * the running status and the encoding of the MIDI events, see [`write`](crate::write);
+    /// * missing CTAB special bytes, which are written as zeros, see [`Ctab::write`];
+    /// * the order of the text chunks of the Music Finder records, see [`Record::write`].
+    ///
+    /// Fails if a table or record cannot be encoded.
+    pub fn to_bytes(&self) -> StdResult<Vec<u8>, &'static str> {
+        let mut out = Vec::new();
+        self.write(&mut out)?;
+        Ok(out)
+    }
+
+    /// Same as [`StyleFile::to_bytes`], writing into the given writer.
+    pub fn write<W: Write>(&self, out: &mut W) -> WriteResult<W> {
+        crate::write(&self.header, &self.tracks, out)?;
+        if let Some(casm) = &self.casm {
+            casm.write(out)?;
+        }
+        if let Some(ots) = &self.ots {
+            write_chunk(b"OTSc", ots.raw(), out)?;
+        }
+        for mdb in self.mdb.iter().chain(&self.more_mdbs) {
+            mdb.write(out)?;
+        }
+        if let Some(mh) = &self.mh {
+            write_chunk(b"MHhd", mh.raw(), out)?;
+        }
+        Ok(())
+    }
+
+    /// The Music Finder records of every MDB section, in file order.
+    ///
+    /// Style files normally have a single MDB section, which is the [`mdb`](StyleFile::mdb)
+    /// field. The records of the following ones are listed after its records. Sections that could
+    /// not be read fail the parse in strict mode, and are otherwise skipped.
+    pub fn records(&self) -> impl Iterator<Item = &Record> {
+        self.mdb
+            .iter()
+            .chain(&self.more_mdbs)
+            .flat_map(|mdb| mdb.records())
+    }
+
+    /// Rename the style, by changing the title of the first Music Finder record.
+    ///
+    /// If the style has no MDB section, or an empty one, a record is created with the first tempo
+    /// and time signature events of the first track, or 120 bpm and 4/4 if there are none.
+    /// Fails if the title is not valid for the record, see [`Record::set_title`].
+    // `Option::is_none_or` needs Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn rename(&mut self, new_title: &str) -> Result<()> {
+        if self
+            .mdb
+            .as_ref()
+            .map_or(true, |mdb| mdb.records().is_empty())
+        {
+            let mut tempo = None;
+            let mut signature = None;
+            for event in self.tracks.first().into_iter().flatten() {
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::Tempo(t)) => {
+                        tempo.get_or_insert(t);
+                    }
+                    TrackEventKind::Meta(MetaMessage::TimeSignature(upper, denom_pow, _, _)) => {
+                        signature
+                            .get_or_insert((upper, 1u8.checked_shl(denom_pow.into()).unwrap_or(4)));
+                    }
+                    _ => {}
+                }
+            }
+            let tempo = tempo.unwrap_or_else(|| u24::new(500_000));
+            let (upper, lower) = signature.unwrap_or((4, 4));
+            let record = Record::new(tempo, upper, lower);
+            self.mdb = Some(Mdb::new(vec![record]));
+        }
+        let mdb = self.mdb.as_mut().expect("missing MDB section");
+        mdb.records_mut()[0].set_title(new_title)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Every accompaniment table of the style, in file order.
+    pub fn ctabs(&self) -> impl Iterator<Item = &Ctab<'a>> {
+        self.casm
+            .iter()
+            .flat_map(|casm| casm.csegs())
+            .flat_map(|cseg| cseg.ctabs())
+    }
+
+    /// Consume the style, yielding every accompaniment table in file order.
+    ///
+    /// The tables do not borrow from the parsed bytes, which can be dropped afterwards.
+    pub fn into_ctabs(self) -> Vec<OwnedCtab> {
+        self.casm
+            .into_iter()
+            .flat_map(Casm::into_ctabs)
+            .map(Ctab::into_owned)
+            .collect()
+    }
+
+    /// The chord types played by at least one accompaniment table of the style.
+    ///
+    /// [`Chord::Cancel`] and the special flags of the chord mute field are not chord types, and
+    /// are never included.
+    pub fn supported_chords(&self) -> BTreeSet<Chord> {
+        let ctabs = self.ctabs().collect::<Vec<_>>();
+        Chord::ALL
+            .iter()
+            .copied()
+            .filter(|chord| chord.is_chord_type())
+            .filter(|&chord| ctabs.iter().any(|ctab| ctab.plays_chord(chord)))
+            .collect()
+    }
+
+    /// Count the style sections and their elements, without decoding them.
+    ///
+    /// Only the chunk structure of the file is walked, which makes this function cheap enough to
+    /// run over whole collections of style files.
+    pub fn scan_counts(raw: &[u8]) -> Result<ScanCounts> {
+        if raw.get(..4) != Some(&b"MThd"[..]) {
+            bail!(err_invalid!("not a style file"));
+        }
+        fn count(chunks: ChunkIter, counts: &mut ScanCounts) -> Result<()> {
+            for chunk in chunks {
+                let chunk = chunk.context(err_malformed!("failed to read chunk"))?;
+                match chunk {
+                    Chunk::Casm(..) => counts.casm += 1,
+                    Chunk::Cseg(..) => counts.cseg += 1,
+                    Chunk::Ctab1(..) | Chunk::Ctab2(..) => counts.ctab += 1,
+                    Chunk::Record(..) => counts.record += 1,
+                    Chunk::Ots(..) => counts.ots += 1,
+                    _ => {}
+                }
+                if let Some(sub_chunks) = chunk.sub_chunks() {
+                    count(sub_chunks, counts)?;
+                }
+            }
+            Ok(())
+        }
+        let mut counts = ScanCounts::default();
+        count(ChunkIter::new(raw), &mut counts)?;
+        Ok(counts)
+    }
+
+    /// Compare the declared length of each top-level chunk with the amount of bytes the parser
+    /// actually understands, in file order.
+    ///
+    /// Container chunks (`CASM`, `CSEG`, `OTSc`, `FNRc` and `FNRP`) are expected to be fully
+    /// covered by their sub-chunks, and `MThd` by the 6 header bytes.
+    /// Midi tracks are considered consumed up to their last readable event.
+    /// The content of other chunks is opaque and always considered consumed.
+    pub fn chunk_report(&self) -> Vec<ChunkReport> {
+        let mut reports = Vec::new();
+        let mut raw = self.raw;
+        while let Ok(Some(RawChunk { id, declared, data })) = RawChunk::read(&mut raw) {
+            let consumed = consumed_len(id, data);
+            reports.push(ChunkReport {
+                id,
+                declared,
+                consumed,
+                mismatch: consumed != declared,
+            });
+        }
+        reports
+    }
+}
+
+/// Assemble a style file from its parts.
+///
+/// Only the sections that were given are written, in the recommended order, as done by
+/// [`StyleFile::write`]. Without any track, the MIDI section holds a single empty track.
+///
+/// ```rust
+/// use midly::style::{Record, StyleFile, StyleFileBuilder};
+/// use midly::num::u24;
+///
+/// let mut record = Record::new(u24::new(500_000), 4, 4);
+/// record.set_title("My style").unwrap();
+/// let bytes = StyleFileBuilder::new().record(record).build().unwrap();
+/// let style = StyleFile::parse(&bytes).unwrap();
+/// assert_eq!(style.records().next().unwrap().title(), "My style");
+/// ```
+#[derive(Clone, Debug)]
+pub struct StyleFileBuilder<'a> {
+    header: Header,
+    tracks: Vec<Track<'a>>,
+    csegs: Vec<Cseg<'a>>,
+    ots: Option<&'a [u8]>,
+    records: Vec<Record>,
+    mh: Option<&'a [u8]>,
+}
+
+impl<'a> StyleFileBuilder<'a> {
+    /// A builder for a style with a single track format header, at 1920 ticks per beat.
+    pub fn new() -> StyleFileBuilder<'a> {
+        StyleFileBuilder {
+            header: Header::new(Format::SingleTrack, Timing::Metrical(u15::new(1920))),
+            tracks: Vec::new(),
+            csegs: Vec::new(),
+            ots: None,
+            records: Vec::new(),
+            mh: None,
+        }
+    }
+
+    /// Use the given MIDI header.
+    pub fn header(mut self, header: Header) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Add a MIDI track.
+    pub fn track(mut self, track: Track<'a>) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    /// Add a style segment to the CASM section.
+    pub fn cseg(mut self, cseg: Cseg<'a>) -> Self {
+        self.csegs.push(cseg);
+        self
+    }
+
+    /// Use the given content for the OTS section, as returned by [`Ots::raw`].
+    pub fn ots(mut self, raw: &'a [u8]) -> Self {
+        self.ots = Some(raw);
+        self
+    }
+
+    /// Add a record to the Music Finder section.
+    pub fn record(mut self, record: Record) -> Self {
+        self.records.push(record);
+        self
+    }
+
+    /// Use the given content for the MH section, as returned by [`Mh::raw`].
+    ///
+    /// The purpose of this section is unknown: it usually holds the MH version, as in
+    /// `[0x00, 0x00, 0x00, 0x01]`.
+    pub fn mh(mut self, raw: &'a [u8]) -> Self {
+        self.mh = Some(raw);
+        self
+    }
+
+    /// Encode the style file.
+    ///
+    /// Fails if a table or record cannot be encoded, see [`StyleFile::to_bytes`]. Every table
+    /// and record is checked before failing, so that all of their errors are returned at once,
+    /// in the order they were added.
+    pub fn build(self) -> StdResult<Vec<u8>, Vec<&'static str>> {
+        let ctabs = self.csegs.iter().flat_map(|cseg| cseg.ctabs());
+        let errors: Vec<_> = ctabs
+            .map(|ctab| ctab.write(&mut WriteCounter(0)))
+            .chain(
+                self.records
+                    .iter()
+                    .map(|record| record.write(&mut WriteCounter(0))),
+            )
+            .filter_map(StdResult::err)
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let tracks = match self.tracks.is_empty() {
+            true => vec![vec![TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            }]],
+            false => self.tracks,
+        };
+        let style = StyleFile {
+            header: self.header,
+            tracks,
+            casm: Some(Casm::new(self.csegs)).filter(|casm| !casm.is_empty()),
+            ots: self.ots.map(Ots::new),
+            mdb: Some(Mdb::new(self.records)).filter(|mdb| !mdb.is_empty()),
+            mh: self.mh.map(Mh::new),
+            more_mdbs: Vec::new(),
+            raw: &[],
+            dirty: false,
+        };
+        style.to_bytes().map_err(|err| vec![err])
+    }
+}
+
+impl Default for StyleFileBuilder<'_> {
+    fn default() -> Self {
+        StyleFileBuilder::new()
+    }
+}
+
+/// Number of style sections and elements in a style file, as returned by
+/// [`StyleFile::scan_counts`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScanCounts {
+    /// Number of CASM sections.
+    pub casm: usize,
+    /// Number of style segments (CSEG), across all CASM sections.
+    pub cseg: usize,
+    /// Number of accompaniment tables (CTAB), across all style segments.
+    pub ctab: usize,
+    /// Number of Music Finder records, across all MDB sections.
+    pub record: usize,
+    /// Number of One Touch Settings sections.
+    pub ots: usize,
+}
+
+/// Something that was skipped while parsing a style file, as returned by
+/// [`StyleFile::parse_lenient`].
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// Four character identifier of the section the error was found in, such as `CASM`, or
+    /// `MTrk` for the MIDI tracks.
+    pub section: [u8; 4],
+    /// The error that was recovered from.
+    pub error: Error,
+}
+
+/// Length sanity report of a single top-level chunk, as returned by
+/// [`StyleFile::chunk_report`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkReport {
+    /// Four character identifier of the chunk, such as `CASM`.
+    pub id: [u8; 4],
+    /// Length of the chunk content, as declared in the chunk header.
+    pub declared: u32,
+    /// Amount of bytes of the chunk content that could be parsed.
+    pub consumed: u32,
+    /// Whether `declared` and `consumed` differ.
+    pub mismatch: bool,
+}
+
+// Count the bytes of a chunk content the parser understands.
+fn consumed_len(id: [u8; 4], data: &[u8]) -> u32 {
+    let consumed = match &id {
+        b"MThd" => data.len().min(6),
+        b"MTrk" => {
+            let mut events = EventIter::new(data);
+            let mut unread = data.len();
+            while let Some(Ok(_)) = events.next() {
+                unread = events.unread().len();
+            }
+            data.len() - unread
+        }
+        b"CASM" | b"CSEG" | b"OTSc" | b"FNRc" => framed_len(data),
+        // Tempo and signature, followed by the text chunks
+        b"FNRP" if data.len() >= 5 => 5 + framed_len(&data[5..]),
+        _ => data.len(),
+    };
+    consumed as u32
+}
+
+// Count the bytes of `data` covered by complete sub-chunks.
+fn framed_len(mut data: &[u8]) -> usize {
+    let mut consumed = 0;
+    while let Ok(Some(chunk)) = RawChunk::read(&mut data) {
+        if chunk.truncated() {
+            break;
+        }
+        consumed += chunk.total_len();
+    }
+    consumed
+}
+
+impl StyleFile<'_> {
+    /// The optional sections present in this style, whether they are empty or not.
+    ///
+    /// A section is absent when the file has no chunk for it. Use the `is_empty` method of a
+    /// section to tell whether it has any content.
+    pub fn sections(&self) -> SectionFlags {
+        let flags = [
+            (self.casm.is_some(), SectionFlags::CASM),
+            (self.ots.is_some(), SectionFlags::OTS),
+            (self.mdb.is_some(), SectionFlags::MDB),
+            (self.mh.is_some(), SectionFlags::MH),
+        ];
+        flags
+            .iter()
+            .filter(|(present, _)| *present)
+            .fold(SectionFlags::NONE, |flags, (_, flag)| flags | *flag)
+    }
+
+    /// A fingerprint of the musical content of this style, to find the same style saved under
+    /// different names.
+    ///
+    /// The following is hashed:
+    /// * the MIDI header and the events of every track, as they are written to a file, except
+    ///   for the track names: the style name is stored as the name of the first track;
+    /// * for each style segment of the CASM section, its style parts and, for each of its tables,
+    ///   the fields compared by [`Ctab::equivalent_for_playback`].
+    ///
+    /// The Music Finder records (title, genre, keywords, tempo), the One Touch Settings, the MH
+    /// section and the table names, editable flags and special bytes are not.
+    /// The hash function is FNV-1a over these bytes and fixed-width counts, so the result does
+    /// not depend on the process nor on the platform, but the value may change across versions
+    /// of this crate.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        hasher.write(&self.header.encode(self.tracks.len() as u16));
+        let mut raw = Vec::new();
+        for track in &self.tracks {
+            raw.clear();
+            let mut running_status = None;
+            for event in track {
+                if let TrackEventKind::Meta(MetaMessage::TrackName(_)) = event.kind {
+                    continue;
+                }
+                // Events that cannot be written, such as oversized meta events, are left out
+                let len = raw.len();
+                if event.write(&mut running_status, &mut raw).is_err() {
+                    raw.truncate(len);
+                }
+            }
+            hasher.write(&(raw.len() as u32).to_be_bytes());
+            hasher.write(&raw);
+        }
+        for cseg in self.casm.iter().flat_map(|casm| casm.csegs()) {
+            let parts = cseg.style_parts();
+            hasher.write(&(parts.len() as u32).to_be_bytes());
+            for part in parts {
+                hasher.write(&[part.index()]);
+            }
+            hasher.write(&(cseg.ctabs().len() as u32).to_be_bytes());
+            for ctab in cseg.ctabs() {
+                ctab.hash_playback(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// The style parts provided by the CASM section, as a bitmask.
+    ///
+    /// Bit `n` is set if the style part with [index](StylePart::index) `n` is listed by any of
+    /// the style segments.
+    /// A style without CASM section has no style parts.
+    pub fn parts_bitmask(&self) -> u32 {
+        self.casm
+            .iter()
+            .flat_map(|casm| casm.csegs())
+            .flat_map(|cseg| cseg.style_parts())
+            .fold(0, |mask, part| mask | 1 << part.index())
+    }
+
+    /// The accompaniment channels played by a style part, as human channel numbers from `1`
+    /// (Ch1) to `16` (Ch16), in ascending order.
+    ///
+    /// These are the [destination channels](Ctab::dest_channel_number) of the tables of every
+    /// style segment listing `part`, each channel being listed once.
+    pub fn part_channels(&self, part: StylePart) -> Vec<u8> {
+        let channels: BTreeSet<u8> = self
+            .casm
+            .iter()
+            .flat_map(|casm| casm.csegs())
+            .filter(|cseg| cseg.style_parts().contains(&part))
+            .flat_map(|cseg| cseg.ctabs())
+            .map(Ctab::dest_channel_number)
+            .collect();
+        channels.into_iter().collect()
+    }
+
+    /// Locate the style parts in the Midi section, as tick ranges from the start of the tracks.
+    ///
+    /// Each part starts at a marker meta event carrying its name, such as `Main A` or `Intro D`,
+    /// in the first track, and runs until the next marker or the end of the track.
+    /// Other markers, such as the `SInt` initialization section, are not part of the result.
+    pub fn part_ticks(&self) -> Vec<(StylePart, Range<u64>)> {
+        let mut parts = Vec::new();
+        let mut current: Option<(StylePart, u64)> = None;
+        let mut tick = 0_u64;
+        for event in self.tracks.first().into_iter().flatten() {
+            tick += u64::from(event.delta.as_int());
+            if let TrackEventKind::Meta(MetaMessage::Marker(name)) = event.kind {
+                if let Some((part, start)) = current.take() {
+                    parts.push((part, start..tick));
+                }
+                current = StylePart::try_from(name).ok().map(|part| (part, tick));
+            }
+        }
+        if let Some((part, start)) = current {
+            parts.push((part, start..tick));
+        }
+        parts
+    }
+}
+
+impl StyleFile<'_> {
+    /// The SFF version of this style.
+    ///
+    /// The version is normally declared by a `SFF1` or `SFF2` marker in the first track.
+    /// When that marker is missing, it is inferred from the accompaniment tables: tables with
+    /// SFF2 only features (several transposition tables, stored in `Ctb2` chunks) imply SFF2,
+    /// while plain `Ctab` chunks imply SFF1. A style without tables has an unknown version, and
+    /// `None` is returned.
+    pub fn sff_version(&self) -> Option<SffVersion> {
+        let marker = self
+            .tracks
+            .first()
+            .into_iter()
+            .flatten()
+            .find_map(|event| marker_version(event.kind));
+        if marker.is_some() {
+            return marker;
+        }
+        let mut ctabs = self.ctabs().peekable();
+        ctabs.peek()?;
+        if ctabs.any(|ctab| ctab.version() != Version::Ctab1) {
+            Some(SffVersion::V2)
+        } else {
+            Some(SffVersion::V1)
+        }
+    }
+}
+
+// 64-bit FNV-1a, a simple hash that gives the same result in every process.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Set of optional style sections, as returned by [`StyleFile::sections`].
+///
+/// Sets are combined with `|`, and queried with [`SectionFlags::contains`]:
+///
+/// ```rust
+/// use midly::style::{SectionFlags, StyleFile};
+///
+/// let style = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+/// assert!(style.sections().contains(SectionFlags::CASM | SectionFlags::OTS));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SectionFlags(u8);
+
+impl SectionFlags {
+    /// No section.
+    pub const NONE: SectionFlags = SectionFlags(0);
+    /// The CASM section.
+    pub const CASM: SectionFlags = SectionFlags(1 << 0);
+    /// The One Touch Settings section.
+    pub const OTS: SectionFlags = SectionFlags(1 << 1);
+    /// The Music Finder section.
+    pub const MDB: SectionFlags = SectionFlags(1 << 2);
+    /// The MH section.
+    pub const MH: SectionFlags = SectionFlags(1 << 3);
+
+    /// The raw bits of this set, as given by the constants.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether every section of `other` is in this set.
+    pub fn contains(self, other: SectionFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for SectionFlags {
+    type Output = SectionFlags;
+
+    fn bitor(self, rhs: SectionFlags) -> SectionFlags {
+        SectionFlags(self.0 | rhs.0)
+    }
+}
+
+/// Version of the style file format, as returned by [`StyleFile::sff_version`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SffVersion {
+    /// SFF1, the original format.
+    V1,
+    /// SFF2, which adds note ranges with their own transposition tables to the accompaniment
+    /// tables.
+    V2,
+}
+
+// The version declared by a `SFF1` or `SFF2` marker event.
+fn marker_version(kind: TrackEventKind) -> Option<SffVersion> {
+    match kind {
+        TrackEventKind::Meta(MetaMessage::Marker(b"SFF1")) => Some(SffVersion::V1),
+        TrackEventKind::Meta(MetaMessage::Marker(b"SFF2")) => Some(SffVersion::V2),
+        _ => None,
+    }
+}
+
+/// Detect the SFF version of a style file without parsing it as a whole.
+///
+/// This looks for the same marker as [`StyleFile::sff_version`], then at the accompaniment
+/// tables of the first CASM section. Only the chunk headers, the events of the first track and
+/// the chunk ids of the tables are read, which makes it a cheap way to sort large style
+/// libraries. As the tables are told apart by their chunk id alone, tables that
+/// [`StyleFile::parse`] would drop as malformed are still counted, so both may disagree on
+/// damaged files.
+///
+/// Fails if the bytes are not a style file, or if the version cannot be told.
+pub fn detect_sff_version(raw: &[u8]) -> Result<SffVersion> {
+    let mut chunks = ChunkIter::new(check_magic(raw)?);
+    read_header(&mut chunks)?;
+    let mut first_track = true;
+    let mut casm_found = false;
+    let mut ctab_version = None;
+    for chunk in chunks {
+        match chunk.context(err_invalid!("invalid style chunk"))? {
+            Chunk::Track(data) if first_track => {
+                first_track = false;
+                let mut events = EventIter::new(data);
+                while let Some(Ok(event)) = events.next() {
+                    if let Some(version) = marker_version(event.kind) {
+                        return Ok(version);
+                    }
+                }
+            }
+            // Only the first CASM section is used
+            Chunk::Casm(data) if !casm_found => {
+                casm_found = true;
+                let tables = ChunkIter::new(data)
+                    .filter_map(|cseg| cseg.ok()?.sub_chunks())
+                    .flatten()
+                    .filter_map(|chunk| chunk.ok());
+                for table in tables {
+                    match table {
+                        Chunk::Ctab2(..) => ctab_version = Some(SffVersion::V2),
+                        Chunk::Ctab1(..) => {
+                            ctab_version.get_or_insert(SffVersion::V1);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    ctab_version.ok_or_else(|| err_invalid!("could not detect the style version").into())
+}
+
+/// Same as [`StyleFile::parse`], so that a style file can be parsed with `try_from`.
+///
+/// ```rust
+/// use std::convert::TryFrom;
+/// use midly::StyleFile;
+///
+/// # fn main() -> midly::Result<()> {
+/// let bytes = include_bytes!("../test-asset/sff1.prs");
+/// let style = StyleFile::try_from(&bytes[..])?;
+/// assert!(style.casm.is_some());
+/// # Ok(())
+/// # }
+/// ```
+impl<'a> TryFrom<&'a [u8]> for StyleFile<'a> {
+    type Error = Error;
+
+    fn try_from(raw: &'a [u8]) -> Result<StyleFile<'a>> {
+        StyleFile::parse(raw)
+    }
+}
+
+/// Parse a raw STYLE file, yielding is SMF component and any optional data part.
+///
+/// The Midi data is parsed as a Header chunk and a track event iterator.
+/// All the Yamaha style chunks are provided as [`Option`].
+/// Oldest SFF1 style files are converted to the new SFF2 structure. This conversion is reversible.
+/// However SFF2 styles cannot be converted to SFF1 without loss of information.
+#[allow(clippy::type_complexity)]
+pub fn parse_style(
+    raw: &[u8],
+) -> Result<(
+    Header,
+    TrackIter<'_>,
+    Option<Casm<'_>>,
+    Option<Ots<'_>>,
+    Option<Mdb>,
+    Option<Mh<'_>>,
+)> {
+    parse_sections(raw, &ParseOptions::default())
+}
+
+#[allow(clippy::type_complexity)]
+fn parse_sections<'a>(
+    raw: &'a [u8],
+    options: &ParseOptions,
+) -> Result<(
+    Header,
+    TrackIter<'a>,
+    Option<Casm<'a>>,
+    Option<Ots<'a>>,
+    Option<Mdb>,
+    Option<Mh<'a>>,
+)> {
+    let mut chunks = ChunkIter::new(check_magic(raw)?);
+    // First chunks should be: 1) Midi header chunk, 2) Tracks chunk
+    let (header, track_count) = read_header(&mut chunks)?;
+    if options.strict() {
+        check_section_order(chunks.clone())?;
+    }
+    // We need one iterator for each section of the style file.
+    // We are just cloning the pointer, so this operation should be cheap.
+    let casm = Casm::parse(chunks.clone(), options)?;
+    let ots = Ots::parse(chunks.clone())?;
+    let mdb = Mdb::parse(chunks.clone(), options)?;
+    let mh = Mh::parse(chunks.clone())?;
+    let tracks = chunks.as_tracks(track_count);
+
+    Ok((header, tracks, casm, ots, mdb, mh))
+}
+
+// Check that the sections follow the recommended order: tracks, CASM, OTS, MDB then MH.
+// Malformed chunks are left to the section parsers.
+fn check_section_order(chunks: ChunkIter) -> Result<()> {
+    let mut last = 0;
+    for chunk in chunks.flatten() {
+        let rank = match chunk {
+            Chunk::Track(..) => 0,
+            Chunk::Casm(..) => 1,
+            Chunk::Ots(..) => 2,
+            Chunk::Mdb(..) => 3,
+            Chunk::Mh(..) | Chunk::MhTrack(..) => 4,
+            _ => continue,
+        };
+        ensure!(rank >= last, err_invalid!("style sections out of order"));
+        last = rank;
+    }
+    Ok(())
+}
+
+// Check that the raw bytes start like a style file.
+fn check_magic(raw: &[u8]) -> Result<&[u8]> {
+    match raw.get(..4) {
+        Some(b"MThd") => Ok(raw),
+        _ => bail!(err_invalid!("not a style file")),
+    }
+}
+
+// Read the Midi header chunk off the front of the chunks.
+fn read_header(chunks: &mut ChunkIter) -> Result<(Header, u16)> {
+    match chunks.next() {
+        Some(maybe_chunk) => match maybe_chunk.context(err_invalid!("invalid midi header"))? {
+            Chunk::Header(header, track_count) => Ok((header, track_count)),
+            _ => bail!(err_invalid!(
+                "expected midi header, found another chunk type"
+            )),
+        },
+        None => bail!(err_invalid!("no midi header chunk")),
+    }
+}
@@ -151,7 +151,7 @@ mod parse_bytemap {
         //NOT consecutive (because delta times must interrupt every single event)
         for (bytes, _ev) in track.iter() {
             let mut advanced = false;
-            while !raw.starts_with(*bytes) {
+            while !raw.starts_with(bytes) {
                 advanced = true;
                 match raw.get(1..) {
                     Some(new_raw) => raw = new_raw,
@@ -166,12 +166,13 @@ mod parse_bytemap {
 }
 mod parse_lazy {
     use super::*;
+    #[allow(dead_code)]
     pub struct Smf<'a> {
         pub header: crate::Header,
         pub tracks: crate::TrackIter<'a>,
     }
     impl Smf<'_> {
-        pub fn parse(raw: &[u8]) -> MidlyResult<Smf> {
+        pub fn parse(raw: &[u8]) -> MidlyResult<Smf<'_>> {
             let (header, tracks) = crate::parse(raw)?;
             Ok(Smf { header, tracks })
         }
@@ -186,18 +187,26 @@ mod parse_lazy {
 
 mod parse_lazy_style {
     use super::*;
+    #[allow(dead_code)]
     pub struct Sff<'a> {
         pub header: crate::Header,
         pub tracks: crate::TrackIter<'a>,
-        pub casm: Option<crate::casm::Casm<'a>>,
-        pub ots: Option<crate::ots::Ots<'a>>,
-        pub mdb: Option<crate::mdb::Mdb<'a>>,
-        pub mh: Option<crate::mh::Mh<'a>>,
+        pub casm: Option<crate::style::Casm<'a>>,
+        pub ots: Option<crate::style::Ots<'a>>,
+        pub mdb: Option<crate::style::Mdb>,
+        pub mh: Option<crate::style::Mh<'a>>,
     }
     impl Sff<'_> {
-        pub fn parse(raw: &[u8]) -> MidlyResult<Sff> {
+        pub fn parse(raw: &[u8]) -> MidlyResult<Sff<'_>> {
             let (header, tracks, casm, ots, mdb, mh) = crate::parse_style(raw)?;
-            Ok(Sff { header, tracks, casm, ots, mdb, mh })
+            Ok(Sff {
+                header,
+                tracks,
+                casm,
+                ots,
+                mdb,
+                mh,
+            })
         }
     }
     pub fn len(_raw: &[u8], track: MidlyResult<EventIter>) -> usize {
@@ -701,3 +710,2947 @@ mod parse {
         assert_send::<crate::Arena>();
     }
 }
+
+/// Test the style file parser on hand-picked chunks.
+mod style {
+    use crate::{
+        casm::{Casm, Cseg, CsegIter, CsegWarning, OwnedCseg},
+        ctab::{
+            pitch_name, Accidental, AccompanimentPart, Chord, Ctab, Key, LostFeature, NoteAction,
+            RangeSlot, RetriggerRule, Table, Third, TranspositionTable, TranspositionType, Version,
+        },
+        mdb::{read_string, Mdb, RecordIter},
+        prelude::*,
+        smf::{Chunk, ChunkIter, RawChunk},
+        style::{
+            Diagnostic, EffectSetting, Encoding, MeterClass, ParseOptions, Record, SectionFlags,
+            SffVersion, Signature, StyleCategory, StyleFile, StyleFileBuilder, StylePart,
+        },
+        Error, MetaMessage, TrackEvent, TrackEventKind,
+    };
+    use std::cell::RefCell;
+
+    /// Drum CTAB from `sff1.prs`, with the autostart bit set.
+    const CTAB1_DRUMS: &[u8] = &[
+        0x09, 0x44, 0x72, 0x73, 0x20, 0x20, 0x20, 0x20, 0x20, 0x09, 0x01, 0x0F, 0xFF, 0x07, 0xFF,
+        0xFF, 0xFF, 0xFF, 0x00, 0x02, 0x01, 0x00, 0x06, 0x00, 0x7F, 0x01, 0x00,
+    ];
+    /// Chord CTAB from `sff1.prs`, without the autostart bit.
+    const CTAB1_CHORD: &[u8] = &[
+        0x00, 0x50, 0x66, 0x20, 0x4C, 0x20, 0x31, 0x20, 0x20, 0x0B, 0x01, 0x0F, 0xFF, 0x03, 0xBD,
+        0xBE, 0xCF, 0x8F, 0x00, 0x02, 0x01, 0x02, 0x07, 0x00, 0x7F, 0x01, 0x00,
+    ];
+    /// Bass CTAB from `sff2.prs`, with bass mode on.
+    const CTAB2_BASS: &[u8] = &[
+        0x0A, 0x45, 0x2E, 0x42, 0x61, 0x73, 0x73, 0x20, 0x20, 0x0A, 0x01, 0x0F, 0xFF, 0x03, 0xFF,
+        0xFF, 0xFF, 0xFF, 0x00, 0x02, 0x00, 0x7F, 0x00, 0x81, 0x03, 0x1C, 0x7F, 0x04, 0x00, 0x81,
+        0x03, 0x1C, 0x7F, 0x02, 0x00, 0x81, 0x03, 0x1C, 0x7F, 0x04, 0x00, 0x00, 0x00, 0x00, 0x80,
+        0x00, 0x00,
+    ];
+
+    /// High-level description of a CTAB chunk, to build test data without assembling bytes.
+    ///
+    /// Every chord type plays by default, see `chord_mute` to change it.
+    struct CtabSpec {
+        version: Version,
+        source: u8,
+        name: &'static str,
+        dest: u8,
+        editable: bool,
+        muted_notes: Vec<Key>,
+        /// Raw chord mute field, as the chord mute bits follow no chord order.
+        chord_mute: [u8; 5],
+        source_chord: Key,
+        source_chord_type: Chord,
+        /// Mid zone, SFFv2 only.
+        range: (u8, u8),
+        tables: Vec<TableSpec>,
+        /// SFFv1 special bytes, flag included, or SFFv2 special bytes.
+        special: Vec<u8>,
+    }
+
+    #[derive(Clone)]
+    struct TableSpec {
+        ntr: TranspositionType,
+        /// Raw transposition table, whose value depends on the version.
+        ntt: u8,
+        bass_on: bool,
+        high_key: Key,
+        note_range: (u8, u8),
+        retrigger_rule: RetriggerRule,
+    }
+
+    impl Default for TableSpec {
+        fn default() -> Self {
+            TableSpec {
+                ntr: TranspositionType::RootTransposition,
+                ntt: 0x00,
+                bass_on: false,
+                high_key: Key::Fs,
+                note_range: (0x00, 0x7F),
+                retrigger_rule: RetriggerRule::PitchShift,
+            }
+        }
+    }
+
+    impl CtabSpec {
+        /// A SFFv1 table with a single default transposition table.
+        fn v1(source: u8, dest: u8, name: &'static str) -> CtabSpec {
+            CtabSpec {
+                version: Version::Ctab1,
+                source,
+                name,
+                dest,
+                editable: true,
+                muted_notes: Vec::new(),
+                chord_mute: [0x03, 0xFF, 0xFF, 0xFF, 0xFF],
+                source_chord: Key::C,
+                source_chord_type: Chord::Maj7,
+                range: (0x00, 0x7F),
+                tables: vec![TableSpec::default()],
+                special: vec![0x00],
+            }
+        }
+
+        /// A SFFv2 table with three default transposition tables.
+        fn v2(source: u8, dest: u8, name: &'static str) -> CtabSpec {
+            CtabSpec {
+                version: Version::Ctab2,
+                tables: vec![TableSpec::default(); 3],
+                special: vec![0x00; 7],
+                ..CtabSpec::v1(source, dest, name)
+            }
+        }
+
+        /// The content of the chunk.
+        fn bytes(&self) -> Vec<u8> {
+            let mut raw = vec![self.source];
+            raw.extend(format!("{:8}", self.name).bytes().take(8));
+            raw.push(self.dest);
+            raw.push(if self.editable { 0x00 } else { 0x01 });
+            let played = Key::ALL
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| !self.muted_notes.contains(key))
+                .fold(0_u16, |bits, (bit, _)| bits | 1 << bit);
+            raw.extend(played.to_be_bytes());
+            raw.extend(self.chord_mute);
+            raw.push(self.source_chord as u8);
+            raw.push(self.source_chord_type as u8);
+            if self.version != Version::Ctab1 {
+                raw.extend([self.range.0, self.range.1]);
+            }
+            for table in &self.tables {
+                raw.extend([
+                    table.ntr as u8,
+                    table.ntt | (table.bass_on as u8) << 7,
+                    table.high_key as u8,
+                    table.note_range.0,
+                    table.note_range.1,
+                    table.retrigger_rule as u8,
+                ]);
+            }
+            raw.extend(&self.special);
+            raw
+        }
+
+        /// The whole chunk, header included.
+        fn chunk(&self) -> Vec<u8> {
+            let id = match self.version {
+                Version::Ctab1 => b"Ctab",
+                _ => b"Ctb2",
+            };
+            chunk(id, &self.bytes())
+        }
+    }
+
+    #[test]
+    fn ctab_spec() {
+        // The spec of a table gives back its bytes
+        let mut spec = CtabSpec::v1(0x00, 0x0B, "Pf L 1");
+        spec.editable = false;
+        spec.chord_mute = [0x03, 0xBD, 0xBE, 0xCF, 0x8F];
+        spec.tables[0] = TableSpec {
+            ntr: TranspositionType::RootFixed,
+            ntt: 0x02,
+            high_key: Key::G,
+            ..TableSpec::default()
+        };
+        assert_eq!(spec.bytes(), CTAB1_CHORD);
+
+        let mut spec = CtabSpec::v2(0x08, 0x0D, "Pad");
+        spec.muted_notes = vec![Key::Cs, Key::B];
+        spec.range = (0x30, 0x47);
+        spec.tables[2].bass_on = true;
+        spec.tables[2].ntt = 0x01;
+        let ctab = Ctab::read(Chunk::Ctab2(&spec.bytes()))
+            .unwrap()
+            .into_owned();
+        assert_eq!(ctab.name(), "Pad");
+        assert_eq!(ctab.dest_role(), Some(AccompanimentPart::Pad));
+        assert!(ctab.is_note_muted(Key::Cs) && ctab.is_note_muted(Key::B));
+        assert!(!ctab.is_note_muted(Key::C));
+        assert!(Chord::ALL
+            .iter()
+            .filter(|chord| chord.is_chord_type())
+            .all(|&chord| ctab.plays_chord(chord)));
+        assert_eq!(
+            ctab.range_boundaries(),
+            Some((u7::new(0x30), u7::new(0x47)))
+        );
+        assert!(ctab.tables()[2].bass_on());
+        assert_eq!(ctab.tables()[2].ntt(), TranspositionTable::Melody);
+        assert_eq!(ctab.special(), Some(&[0x00; 7][..]));
+        let mut out = Vec::new();
+        ctab.write(&mut out).unwrap();
+        assert_eq!(out, spec.chunk());
+    }
+
+    #[test]
+    fn drums_autostart() {
+        let drums = Ctab::read(Chunk::Ctab1(CTAB1_DRUMS)).unwrap();
+        assert!(drums.drums_autostart());
+        let chord = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        assert!(!chord.drums_autostart());
+    }
+
+    #[test]
+    fn ctab_equivalent_for_playback() {
+        let chord = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        // Editable, and renamed
+        let mut raw = CTAB1_CHORD.to_vec();
+        raw[10] = 0x00;
+        raw[1..9].copy_from_slice(b"Renamed ");
+        let edited = Ctab::read(Chunk::Ctab1(&raw)).unwrap();
+        assert_ne!(chord.editable(), edited.editable());
+        assert!(chord.equivalent_for_playback(&edited));
+        assert!(edited.equivalent_for_playback(&chord));
+
+        // Another source chord
+        raw[18] = 0x02;
+        let moved = Ctab::read(Chunk::Ctab1(&raw)).unwrap();
+        assert!(!chord.equivalent_for_playback(&moved));
+        let drums = Ctab::read(Chunk::Ctab1(CTAB1_DRUMS)).unwrap();
+        assert!(!chord.equivalent_for_playback(&drums));
+    }
+
+    #[test]
+    fn ctab_debug_is_ordered() {
+        let chord = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        let dbg = format!("{:?}", chord);
+        assert!(dbg.contains("note_mute: {C: false, Cs: false, D: false, Eb: false"));
+        assert!(dbg.contains("chord_mute: {Maj: true, Maj6: true, Maj7: true"));
+        for _ in 0..8 {
+            let again = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+            assert_eq!(format!("{:?}", again), dbg);
+        }
+    }
+
+    #[test]
+    fn style_part_order() {
+        let mut parts = vec![
+            StylePart::EndingA,
+            StylePart::FillInBA,
+            StylePart::MainB,
+            StylePart::IntroD,
+            StylePart::FillInAA,
+            StylePart::MainA,
+            StylePart::IntroA,
+        ];
+        parts.sort();
+        assert_eq!(
+            parts,
+            [
+                StylePart::IntroA,
+                StylePart::IntroD,
+                StylePart::MainA,
+                StylePart::MainB,
+                StylePart::FillInAA,
+                StylePart::FillInBA,
+                StylePart::EndingA,
+            ]
+        );
+        let set: std::collections::HashSet<_> = parts.iter().collect();
+        assert_eq!(set.len(), parts.len());
+    }
+
+    #[test]
+    fn style_category_parts() {
+        assert_eq!(
+            StyleCategory::Main.parts(),
+            &[
+                StylePart::MainA,
+                StylePart::MainB,
+                StylePart::MainC,
+                StylePart::MainD
+            ]
+        );
+        assert!(StyleCategory::FillIn.parts().contains(&StylePart::FillInBA));
+        let categories = [
+            StyleCategory::Intro,
+            StyleCategory::Main,
+            StyleCategory::FillIn,
+            StyleCategory::Ending,
+        ];
+        let mut count = 0;
+        for category in &categories {
+            for part in category.parts() {
+                assert_eq!(part.category(), *category);
+                count += 1;
+            }
+        }
+        assert_eq!(count, 17);
+    }
+
+    #[test]
+    fn style_part_index() {
+        for index in 0..17 {
+            let part = StylePart::from_index(index).unwrap();
+            assert_eq!(part.index(), index);
+        }
+        assert_eq!(StylePart::from_index(17), None);
+        assert_eq!(StylePart::IntroA.index(), 0);
+        assert_eq!(StylePart::EndingD.index(), 16);
+    }
+
+    #[test]
+    fn parts_bitmask() {
+        let sff1 = StyleFile::parse(include_bytes!("../test-asset/sff1.prs")).unwrap();
+        // Every part but "Intro D" and "Ending D"
+        let expected =
+            0x1_FFFF & !(1 << StylePart::IntroD.index()) & !(1 << StylePart::EndingD.index());
+        assert_eq!(sff1.parts_bitmask(), expected);
+    }
+
+    #[test]
+    fn pitch_names() {
+        assert_eq!(pitch_name(u7::new(60)), "C4");
+        assert_eq!(pitch_name(u7::new(61)), "C♯4");
+        assert_eq!(pitch_name(u7::new(70)), "B♭4");
+        assert_eq!(pitch_name(u7::new(0)), "C-1");
+        assert_eq!(pitch_name(u7::new(127)), "G9");
+
+        let raw = [0x00, 0x01, 0x06, 0x24, 0x60, 0x01];
+        let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+        let (low, high) = table.note_range_u8();
+        assert_eq!((low, high), (36, 96));
+        let range = format!(
+            "{}..{}",
+            pitch_name(u7::new(low)),
+            pitch_name(u7::new(high))
+        );
+        assert_eq!(range, "C2..C7");
+    }
+
+    #[test]
+    fn resolve_root() {
+        // Root transposition, high key F#
+        let raw = [0x00, 0x01, 0x06, 0x00, 0x7F, 0x01];
+        let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+        assert_eq!(table.resolve_root(Key::C), 0);
+        assert_eq!(table.resolve_root(Key::Fs), 6);
+        // Above the high key, the root maps to the octave below
+        assert_eq!(table.resolve_root(Key::G), -5);
+        assert_eq!(table.resolve_root(Key::B), -1);
+
+        // Root fixed, the high key is ignored
+        let raw = [0x01, 0x01, 0x0B, 0x00, 0x7F, 0x01];
+        let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+        assert_eq!(table.ntr(), TranspositionType::RootFixed);
+        assert_eq!(table.resolve_root(Key::Fs), 6);
+        assert_eq!(table.resolve_root(Key::G), -5);
+    }
+
+    #[test]
+    fn inverted_note_range() {
+        // Root transposition, high key F#, note range G3..C3
+        let raw = [0x00, 0x01, 0x06, 0x37, 0x30, 0x01];
+        if cfg!(feature = "strict") {
+            let err = Table::try_from((&raw[..], Version::Ctab1)).unwrap_err();
+            assert_eq!(
+                err.kind().message(),
+                "note range low limit above high limit"
+            );
+        } else {
+            let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+            assert_eq!(table.note_range_u8(), (0x30, 0x37));
+        }
+
+        // Tables are read with the options of their CTAB
+        let mut ctab = CTAB1_CHORD.to_vec();
+        ctab[23..25].copy_from_slice(&[0x37, 0x30]);
+        let skipped = RefCell::new(Vec::new());
+        let on_skip = |err: &Error| skipped.borrow_mut().push(err.kind().message());
+        let options = ParseOptions {
+            on_skip: Some(&on_skip),
+            lenient: true,
+            ..ParseOptions::default()
+        };
+        let ctab = Ctab::read_with(Chunk::Ctab1(&ctab), &options).unwrap();
+        assert_eq!(ctab.tables()[0].note_range_u8(), (0x30, 0x37));
+        assert_eq!(
+            skipped.into_inner(),
+            vec!["note range low limit above high limit"]
+        );
+    }
+
+    #[test]
+    fn unsupported_version() {
+        // Guitar transposition, then a SFF2 transposition table, in SFF1 tables
+        for raw in [
+            [0x02, 0x01, 0x07, 0x00, 0x7F, 0x01],
+            [0x00, 0x06, 0x07, 0x00, 0x7F, 0x01],
+        ] {
+            let table = Table::try_from((&raw[..], Version::Ctab1));
+            if cfg!(feature = "strict") {
+                let err = table.unwrap_err();
+                assert_eq!(err.unsupported_version().unwrap().0, SffVersion::V1);
+            } else {
+                assert!(table.is_ok());
+            }
+            assert!(Table::try_from((&raw[..], Version::Ctab2)).is_ok());
+        }
+        // Other errors are not about the version
+        let raw = [0x00, 0x01, 0x0C, 0x00, 0x7F, 0x01];
+        let err = Table::try_from((&raw[..], Version::Ctab1)).unwrap_err();
+        assert_eq!(err.unsupported_version(), None);
+    }
+
+    #[test]
+    fn v1_bass_flag() {
+        // Chord table with the MSB of the NTT byte set
+        let mut raw = CTAB1_CHORD.to_vec();
+        raw[21] |= 0x80;
+        let ctab = Ctab::read(Chunk::Ctab1(&raw));
+        if cfg!(feature = "strict") {
+            assert_eq!(
+                ctab.unwrap_err().unsupported_version(),
+                Some((SffVersion::V1, "bass mode"))
+            );
+        } else {
+            let ctab = ctab.unwrap();
+            let table = &ctab.tables()[0];
+            assert!(table.v1_bass_flag());
+            assert!(!table.bass_on());
+            assert_eq!(table.ntt(), TranspositionTable::Chord);
+            let mut out = Vec::new();
+            ctab.write(&mut out).unwrap();
+            assert_eq!(out[8..], raw[..]);
+            let v2 = ctab.upgrade_to_v2();
+            assert!(v2
+                .tables()
+                .iter()
+                .all(|t| !t.bass_on() && !t.v1_bass_flag()));
+        }
+        let ctab = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        assert!(!ctab.tables()[0].v1_bass_flag());
+    }
+
+    #[test]
+    fn raw_table_bytes() {
+        let ctab = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        let table = &ctab.tables()[0];
+        assert_eq!((table.raw_ntr_byte(), table.raw_ntt_byte()), (0x01, 0x02));
+
+        // Unknown transposition table, with bass mode on
+        let mut spec = CtabSpec::v2(0x0A, 0x0A, "E.Bass");
+        spec.tables[1].ntt = 0x0F;
+        spec.tables[1].bass_on = true;
+        let raw = spec.bytes();
+        let ctab = Ctab::read(Chunk::Ctab2(&raw));
+        if cfg!(feature = "strict") {
+            assert_eq!(
+                ctab.unwrap_err().kind().message(),
+                "unknown transposition table"
+            );
+        } else {
+            let ctab = ctab.unwrap();
+            let table = &ctab.tables()[1];
+            assert_eq!(table.ntt(), TranspositionTable::Bypass);
+            assert_eq!(table.raw_ntt_byte(), 0x8F);
+            assert!(table.bass_on());
+            // The raw bytes are not compared
+            spec.tables[1].ntt = 0x00;
+            let raw = spec.bytes();
+            let known = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+            assert_eq!(known.tables()[1], *table);
+            assert!(known.equivalent_for_playback(&ctab));
+        }
+    }
+
+    #[test]
+    fn range_flags() {
+        let raw = [0x00, 0x01, 0x07, 0x00, 0x7F, 0x01];
+        let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+        assert_eq!(table.note_range_u8(), (0x00, 0x7F));
+        assert_eq!(table.range_flags(), (false, false));
+
+        let raw = [0x00, 0x01, 0x07, 0x00, 0xFF, 0x01];
+        if cfg!(feature = "strict") {
+            let err = Table::try_from((&raw[..], Version::Ctab1)).unwrap_err();
+            assert_eq!(err.kind().message(), "note range MSB is set");
+        } else {
+            let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+            assert_eq!(table.note_range_u8(), (0x00, 0x7F));
+            assert_eq!(table.range_flags(), (false, true));
+
+            // The flag is written back
+            let mut ctab = CTAB1_CHORD.to_vec();
+            ctab[24] = 0xFF;
+            let mut out = Vec::new();
+            Ctab::read(Chunk::Ctab1(&ctab))
+                .unwrap()
+                .write(&mut out)
+                .unwrap();
+            assert_eq!(out[8 + 24], 0xFF);
+        }
+    }
+
+    #[test]
+    fn source_voicing() {
+        // Recorded on C minor seventh
+        let mut raw = CTAB1_CHORD.to_vec();
+        raw[19] = 0x0A;
+        let ctab = Ctab::read(Chunk::Ctab1(&raw)).unwrap();
+        assert_eq!(ctab.source_chord(), Key::C);
+        assert_eq!(ctab.source_chord_type(), Chord::Min7);
+        let notes: Vec<_> = ctab.source_voicing(4).iter().map(|n| n.as_int()).collect();
+        assert_eq!(notes, [60, 63, 67, 70]);
+        let names: Vec<_> = ctab.source_voicing(3).into_iter().map(pitch_name).collect();
+        assert_eq!(names, ["C3", "E♭3", "G3", "B♭3"]);
+        // Notes above G9 are dropped
+        let notes: Vec<_> = ctab.source_voicing(9).iter().map(|n| n.as_int()).collect();
+        assert_eq!(notes, [120, 123, 127]);
+    }
+
+    #[test]
+    fn rekey() {
+        // Recorded in C, muted on C# and D, high key G, notes C3..C5
+        let mut raw = CTAB1_CHORD.to_vec();
+        raw[12] = 0b1111_1001;
+        raw[23..25].copy_from_slice(&[0x30, 0x48]);
+        let mut ctab = Ctab::read(Chunk::Ctab1(&raw)).unwrap();
+        assert_eq!(ctab.source_chord(), Key::C);
+        ctab.rekey(Key::D);
+        assert_eq!(ctab.source_chord(), Key::D);
+        let muted: Vec<_> = Key::ALL
+            .iter()
+            .filter(|&&key| ctab.is_note_muted(key))
+            .collect();
+        assert_eq!(muted, [&Key::Eb, &Key::E]);
+        assert_eq!(ctab.tables()[0].high_key(), Key::A);
+        assert_eq!(ctab.tables()[0].note_range_u8(), (0x32, 0x4A));
+        // SFFv1 tables always cover every note
+        assert_eq!(ctab.range(), (u7::new(0), u7::new(127)));
+
+        // Back down a tone
+        ctab.rekey(Key::C);
+        assert_eq!(ctab.tables()[0].high_key(), Key::G);
+        assert_eq!(ctab.tables()[0].note_range_u8(), (0x30, 0x48));
+        ctab.rekey(Key::Bb);
+        assert_eq!(ctab.tables()[0].high_key(), Key::F);
+        // Notes are clamped to the MIDI range
+        let mut v2 = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        v2.rekey(Key::Bb);
+        assert_eq!(v2.range(), (u7::new(0), u7::new(0x7D)));
+        assert_eq!(v2.tables()[0].note_range_u8(), (0x1A, 0x7D));
+    }
+
+    #[test]
+    fn wraps_root() {
+        // Root transposition, high key G
+        let raw = [0x00, 0x01, 0x07, 0x00, 0x7F, 0x01];
+        let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+        assert_eq!(table.high_key(), Key::G);
+        assert!(table.wraps_root(Key::A));
+        assert!(table.wraps_root(Key::Gs));
+        assert!(!table.wraps_root(Key::G));
+        assert!(!table.wraps_root(Key::E));
+
+        // Root fixed, the high key is ignored
+        let raw = [0x01, 0x01, 0x07, 0x00, 0x7F, 0x01];
+        let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+        assert!(!table.wraps_root(Key::A));
+    }
+
+    #[test]
+    fn map_range() {
+        // Root transposition, Melody table, high key F#, full note range.
+        let raw = [0x00, 0x01, 0x06, 0x00, 0x7F, 0x01];
+        let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+        assert_eq!(table.ntt(), TranspositionTable::Melody);
+        // C2 to C3, played at G: above the high key, so transposed down a fourth.
+        let mapping = table.map_range(Key::G, u7::new(36)..=u7::new(48));
+        assert_eq!(mapping.len(), 13);
+        for (src, dst) in mapping {
+            assert_eq!(dst.as_int(), src.as_int() - 5);
+        }
+        // At E, the notes are transposed up.
+        assert_eq!(table.transpose(u7::new(36), Key::E), u7::new(40));
+        // Notes are folded into the note range.
+        let raw = [0x00, 0x01, 0x06, 0x30, 0x3B, 0x01];
+        let table = Table::try_from((&raw[..], Version::Ctab1)).unwrap();
+        assert_eq!(table.transpose(u7::new(36), Key::G), u7::new(55));
+    }
+
+    #[test]
+    fn ots_settings() {
+        let sff1 = StyleFile::parse(include_bytes!("../test-asset/sff1.prs")).unwrap();
+        let settings = sff1.ots.unwrap().settings();
+        assert_eq!(settings.len(), 4);
+        let parts = settings.iter().map(|(part, _)| *part).collect::<Vec<_>>();
+        assert_eq!(
+            parts,
+            [
+                Some(StylePart::MainA),
+                Some(StylePart::MainB),
+                Some(StylePart::MainC),
+                Some(StylePart::MainD)
+            ]
+        );
+        for (index, (_, setting)) in settings.iter().enumerate() {
+            assert_eq!(setting.index(), index);
+            assert!(setting.events().count() > 0);
+        }
+    }
+
+    #[test]
+    fn ots_settings_positions() {
+        // A duplicate header in slot 2 is a track that cannot be read in strict mode
+        let setting = chunk(b"MTrk", &[0x00, 0xB0, 0x5B, 0x40, 0x00, 0xFF, 0x2F, 0x00]);
+        let header = chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]);
+        let mut raw = style_bytes(&[]);
+        raw.extend(chunk(
+            b"OTSc",
+            &[&setting[..], &header, &setting, &setting].concat(),
+        ));
+        let ots = StyleFile::parse(&raw).unwrap().ots.unwrap();
+        let settings = ots.settings();
+        let indexes = settings
+            .iter()
+            .map(|(_, setting)| setting.index())
+            .collect::<Vec<_>>();
+        let parts = settings.iter().map(|(part, _)| *part).collect::<Vec<_>>();
+        if cfg!(feature = "strict") {
+            assert_eq!(indexes, [0, 2, 3]);
+            assert_eq!(
+                parts,
+                [
+                    Some(StylePart::MainA),
+                    Some(StylePart::MainC),
+                    Some(StylePart::MainD)
+                ]
+            );
+            assert!(ots.effect_settings(1).is_err());
+        } else {
+            assert_eq!(indexes, [0, 1, 2]);
+        }
+        for &index in &indexes {
+            assert_eq!(ots.effect_settings(index).unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn section_iterators() {
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let casm = sff2.casm.unwrap();
+        let csegs = casm.csegs().len();
+        assert_eq!((&casm).into_iter().count(), csegs);
+        let mut parts = 0;
+        for cseg in casm {
+            parts += cseg.style_parts().len();
+        }
+        assert_eq!(parts, 15);
+
+        let ots = sff2.ots.unwrap();
+        assert_eq!(ots.tracks().count(), ots.settings().len());
+    }
+
+    #[test]
+    fn section_flags() {
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let sections = sff2.sections();
+        assert!(sections.contains(SectionFlags::CASM | SectionFlags::OTS | SectionFlags::MDB));
+        assert_eq!(sections.contains(SectionFlags::MH), sff2.mh.is_some());
+        assert!(!sff2.casm.unwrap().is_empty());
+        assert!(!sff2.ots.unwrap().is_empty());
+        assert!(!sff2.mdb.unwrap().is_empty());
+
+        // Present but empty sections
+        let mut raw = style_bytes(&[]);
+        raw.extend(chunk(b"OTSc", &[]));
+        raw.extend(chunk(b"FNRc", &[]));
+        let style = StyleFile::parse(&raw).unwrap();
+        assert_eq!(
+            style.sections(),
+            SectionFlags::CASM | SectionFlags::OTS | SectionFlags::MDB
+        );
+        assert_eq!(style.sections().bits(), 0b0111);
+        assert!(!style.sections().contains(SectionFlags::MH));
+        assert!(style.ots.unwrap().is_empty());
+        assert!(style.mdb.unwrap().is_empty());
+        assert!(!style.casm.unwrap().is_empty());
+
+        let meta = StyleFile::parse_metadata_only(&raw).unwrap();
+        assert_eq!(meta.sections(), SectionFlags::MDB);
+    }
+
+    #[test]
+    fn section_order() {
+        let raw = [
+            chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]),
+            chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]),
+            chunk(b"OTSc", &[]),
+            chunk(b"CASM", &chunk(b"CSEG", &chunk(b"Sdec", b"Main A"))),
+        ]
+        .concat();
+        let style = StyleFile::parse(&raw);
+        if cfg!(feature = "strict") {
+            assert_eq!(
+                style.unwrap_err().kind().message(),
+                "style sections out of order"
+            );
+        } else {
+            let style = style.unwrap();
+            assert!(style.casm.is_some() && style.ots.is_some());
+        }
+        // The lenient parser accepts any order
+        assert!(StyleFile::parse_lenient(&raw).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn mh_raw() {
+        let mut raw = style_bytes(&[]);
+        raw.extend(chunk(b"MHhd", &[0x00, 0x00, 0x00, 0x01]));
+        let mh = StyleFile::parse(&raw).unwrap().mh.unwrap();
+        assert_eq!(mh.raw(), &[0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(mh.len(), 4);
+        assert!(!mh.is_empty());
+    }
+
+    #[test]
+    fn ots_effect_settings() {
+        let setting = [
+            &[0x00, 0xB0, 0x5B, 0x40][..],
+            &[0x00, 0xB1, 0x5D, 0x20],
+            &[0x00, 0xB1, 0x07, 0x64],
+            &[
+                0x00, 0xF0, 0x09, 0x43, 0x10, 0x4C, 0x02, 0x01, 0x00, 0x01, 0x10, 0xF7,
+            ],
+            &[0x00, 0xB2, 0x5B, 0x00],
+            &[0x00, 0xFF, 0x2F, 0x00],
+        ]
+        .concat();
+        let empty = chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]);
+        let mut raw = style_bytes(&[]);
+        raw.extend(chunk(
+            b"OTSc",
+            &[&empty[..], &chunk(b"MTrk", &setting)].concat(),
+        ));
+        let ots = StyleFile::parse(&raw).unwrap().ots.unwrap();
+        assert_eq!(ots.effect_settings(0).unwrap(), vec![]);
+        assert_eq!(
+            ots.effect_settings(1).unwrap(),
+            vec![
+                EffectSetting::ReverbDepth {
+                    channel: u4::new(0),
+                    depth: u7::new(0x40)
+                },
+                EffectSetting::ChorusDepth {
+                    channel: u4::new(1),
+                    depth: u7::new(0x20)
+                },
+                EffectSetting::ReverbType {
+                    msb: 0x01,
+                    lsb: 0x10
+                },
+                EffectSetting::ReverbDepth {
+                    channel: u4::new(2),
+                    depth: u7::new(0)
+                },
+            ]
+        );
+        assert!(ots.effect_settings(2).is_err());
+    }
+
+    #[test]
+    fn ots_named_settings() {
+        let setting = chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]);
+        let mut raw = style_bytes(&[]);
+        raw.extend(chunk(b"OTSc", &[&setting[..], &setting].concat()));
+        let style = StyleFile::parse(&raw).unwrap();
+        let names = style
+            .ots
+            .unwrap()
+            .named_settings()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["OTS 1", "OTS 2"]);
+
+        // A setting that cannot be read leaves a gap in the labels
+        let header = chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]);
+        let mut raw = style_bytes(&[]);
+        raw.extend(chunk(b"OTSc", &[&setting[..], &header, &setting].concat()));
+        let style = StyleFile::parse(&raw).unwrap();
+        let names = style
+            .ots
+            .unwrap()
+            .named_settings()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        if cfg!(feature = "strict") {
+            assert_eq!(names, ["OTS 1", "OTS 3"]);
+        } else {
+            assert_eq!(names, ["OTS 1", "OTS 2"]);
+        }
+    }
+
+    #[test]
+    fn raw_chunk_total_len() {
+        let raw = [chunk(b"Sdec", b"Main A"), chunk(b"Xxxx", b"odd")].concat();
+        let mut rest = &raw[..];
+        let first = RawChunk::read(&mut rest).unwrap().unwrap();
+        assert_eq!(first.total_len(), 8 + 6);
+        // Odd lengths are not padded
+        let second = RawChunk::read(&mut rest).unwrap().unwrap();
+        assert_eq!(&second.id, b"Xxxx");
+        assert_eq!(second.total_len(), 8 + 3);
+        assert_eq!(first.total_len() + second.total_len(), raw.len());
+        assert!(RawChunk::read(&mut rest).unwrap().is_none());
+
+        // A truncated chunk only counts the bytes present
+        let mut rest = &raw[..raw.len() - 1];
+        RawChunk::read(&mut rest).unwrap();
+        let cut = RawChunk::read(&mut rest).unwrap().unwrap();
+        assert!(cut.truncated());
+        assert_eq!(cut.declared, 3);
+        assert_eq!(cut.total_len(), 8 + 2);
+        // An incomplete header cannot be read
+        assert!(RawChunk::read(&mut &raw[..6]).is_err());
+    }
+
+    #[test]
+    fn chunk_report() {
+        for raw in [
+            &include_bytes!("../test-asset/sff1.prs")[..],
+            &include_bytes!("../test-asset/sff2.prs")[..],
+        ] {
+            let style = StyleFile::parse(raw).unwrap();
+            let report = style.chunk_report();
+            assert_eq!(&report[0].id, b"MThd");
+            assert!(report.iter().any(|chunk| &chunk.id == b"CASM"));
+            for chunk in report {
+                assert!(!chunk.mismatch, "{:?}", chunk);
+            }
+        }
+
+        // Sub-chunks are covered up to the first truncated one
+        let cseg = chunk(b"CSEG", &chunk(b"Sdec", b"Main A"));
+        let casm = [&cseg[..], b"Sdec\x00\x00\x00\x09Main"].concat();
+        let mut raw = style_bytes(&[]);
+        raw.extend(chunk(b"CASM", &casm));
+        let report = StyleFile::parse_lenient(&raw).unwrap().0.chunk_report();
+        let casm_report = report.iter().rev().find(|c| &c.id == b"CASM").unwrap();
+        assert_eq!(casm_report.declared as usize, casm.len());
+        assert_eq!(casm_report.consumed as usize, cseg.len());
+        assert!(casm_report.mismatch);
+    }
+
+    #[test]
+    fn record_bpm() {
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let record = &sff2.mdb.as_ref().unwrap().records()[0];
+        assert_eq!(record.bpm().unwrap().round(), 128.0);
+
+        let zero = Record::read(Chunk::Record(&[0x00, 0x00, 0x00, 0x04, 0x04])).unwrap();
+        assert_eq!(zero.bpm(), None);
+
+        assert_eq!(Record::tempo_from_bpm(120.0), Some(u24::new(500_000)));
+        assert_eq!(Record::tempo_from_bpm(1000.0), Some(u24::new(60_000)));
+        assert_eq!(Record::tempo_from_bpm(0.5), None);
+        assert_eq!(Record::tempo_from_bpm(0.0), None);
+        assert_eq!(Record::tempo_from_bpm(-120.0), None);
+        assert_eq!(Record::tempo_from_bpm(f64::NAN), None);
+    }
+
+    #[test]
+    fn mdb_tempo_range() {
+        let record = |bpm: f64| {
+            let tempo = Record::tempo_from_bpm(bpm).unwrap().as_int().to_be_bytes();
+            chunk(b"FNRP", &[tempo[1], tempo[2], tempo[3], 0x04, 0x04])
+        };
+        let mdb = |records: &[Vec<u8>]| {
+            let raw = chunk(b"FNRc", &records.concat());
+            Mdb::parse(ChunkIter::new(&raw), &ParseOptions::default())
+                .unwrap()
+                .unwrap()
+        };
+        let rounded = |(min, max): (f64, f64)| (min.round(), max.round());
+
+        let range = mdb(&[record(120.0), record(90.0), record(160.0)]).tempo_range();
+        assert_eq!(range.map(rounded), Some((90.0, 160.0)));
+        // A zero tempo is skipped
+        let zero = chunk(b"FNRP", &[0x00, 0x00, 0x00, 0x04, 0x04]);
+        let range = mdb(&[zero.clone(), record(100.0)]).tempo_range();
+        assert_eq!(range.map(rounded), Some((100.0, 100.0)));
+        assert_eq!(mdb(&[zero]).tempo_range(), None);
+        assert_eq!(mdb(&[]).tempo_range(), None);
+    }
+
+    #[test]
+    fn record_write_order() {
+        // Sub-chunks in non canonical order
+        let raw = [
+            &[0x07, 0xA1, 0x20, 0x04, 0x04][..],
+            &chunk(b"Kwd2", b"Dance"),
+            &chunk(b"Gnam", b"Pop"),
+            &chunk(b"Kwd1", b"Party"),
+            &chunk(b"Mnam", b"Title"),
+        ]
+        .concat();
+        let record = Record::read(Chunk::Record(&raw)).unwrap();
+        let mut out = Vec::new();
+        record.write(&mut out).unwrap();
+        let canonical = [
+            &[0x07, 0xA1, 0x20, 0x04, 0x04][..],
+            &chunk(b"Mnam", b"Title"),
+            &chunk(b"Gnam", b"Pop"),
+            &chunk(b"Kwd1", b"Party"),
+            &chunk(b"Kwd2", b"Dance"),
+        ]
+        .concat();
+        assert_eq!(out, chunk(b"FNRP", &canonical));
+        let reread = RecordIter::new(&out).next().unwrap().unwrap();
+        assert_eq!(reread, record);
+        assert_eq!(reread.keyword1(), Some("Party"));
+        assert_eq!(reread.keyword2(), Some("Dance"));
+    }
+
+    #[test]
+    fn read_string_padding() {
+        assert_eq!(
+            read_string(b"E.Bass  ", Encoding::Utf8, true).as_deref(),
+            Some("E.Bass")
+        );
+        assert_eq!(
+            read_string(b"Pad\0\0 \0", Encoding::Utf8, true).as_deref(),
+            Some("Pad")
+        );
+        // Only the trailing padding is removed
+        assert_eq!(
+            read_string(b" Sax  ", Encoding::Utf8, true).as_deref(),
+            Some(" Sax")
+        );
+        assert_eq!(
+            read_string(b"Title  ", Encoding::Utf8, false).as_deref(),
+            Some("Title  ")
+        );
+        assert_eq!(read_string(b"", Encoding::Utf8, true).as_deref(), Some(""));
+        assert_eq!(
+            read_string(b"    ", Encoding::Utf8, true).as_deref(),
+            Some("")
+        );
+        // "さくら" in Shift-JIS
+        let sjis = [0x82, 0xB3, 0x82, 0xAD, 0x82, 0xE7];
+        assert_eq!(read_string(&sjis, Encoding::Utf8, true), None);
+        assert_eq!(
+            read_string(&sjis, Encoding::ShiftJis, true).as_deref(),
+            Some("さくら")
+        );
+    }
+
+    #[test]
+    fn record_duplicate_keyword() {
+        let raw = [
+            &[0x07, 0xA1, 0x20, 0x04, 0x04][..],
+            &chunk(b"Mnam", b"Title"),
+            &chunk(b"Kwd1", b"First"),
+            &chunk(b"Kwd1", b"Second"),
+        ]
+        .concat();
+        if cfg!(feature = "strict") {
+            let err = Record::read(Chunk::Record(&raw)).unwrap_err();
+            assert_eq!(err.kind().message(), "duplicate text chunk in record");
+        }
+        // The first one wins outside of strict mode
+        let skipped = RefCell::new(Vec::new());
+        let on_skip = |err: &Error| skipped.borrow_mut().push(err.kind().message());
+        let options = ParseOptions {
+            on_skip: Some(&on_skip),
+            lenient: true,
+            ..ParseOptions::default()
+        };
+        let record = Record::read_with(Chunk::Record(&raw), &options).unwrap();
+        assert_eq!(record.keyword1(), Some("First"));
+        assert_eq!(record.title(), "Title");
+        assert_eq!(skipped.into_inner(), vec!["duplicate text chunk in record"]);
+    }
+
+    #[test]
+    fn record_encoding_roundtrip() {
+        let raw = [
+            &[0x07, 0xA1, 0x20, 0x03, 0x04][..],
+            &chunk(b"Mnam", "さくら".as_bytes()),
+            &chunk(b"Gnam", b"Ballad"),
+        ]
+        .concat();
+        let record = Record::read(Chunk::Record(&raw)).unwrap();
+        assert_eq!(record.encoding(), Encoding::Utf8);
+        assert_eq!(record.title(), "さくら");
+
+        // UTF-8 records are written back unchanged
+        let mut out = Vec::new();
+        record
+            .write_with_encoding(Encoding::Utf8, &mut out)
+            .unwrap();
+        assert_eq!(out, chunk(b"FNRP", &raw));
+
+        let mut out = Vec::new();
+        record
+            .write_with_encoding(Encoding::ShiftJis, &mut out)
+            .unwrap();
+        let sjis = match ChunkIter::new(&out).next() {
+            Some(Ok(Chunk::Record(data))) => Record::read(Chunk::Record(data)).unwrap(),
+            _ => panic!("expected a record chunk"),
+        };
+        assert_eq!(sjis.encoding(), Encoding::ShiftJis);
+        assert_eq!(sjis.title(), "さくら");
+        assert_eq!(sjis.genre(), "Ballad");
+        assert_eq!(sjis.tempo(), record.tempo());
+        assert_eq!(sjis.signature(), record.signature());
+
+        // Characters without a Shift-JIS representation are rejected
+        let raw = [
+            &[0x07, 0xA1, 0x20, 0x03, 0x04][..],
+            &chunk(b"Mnam", "♫ 🎹".as_bytes()),
+        ]
+        .concat();
+        let record = Record::read(Chunk::Record(&raw)).unwrap();
+        assert!(record
+            .write_with_encoding(Encoding::ShiftJis, &mut Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn style_part_break_alias() {
+        assert_eq!(StylePart::try_from("Break").unwrap(), StylePart::FillInBA);
+        assert_eq!(
+            StylePart::try_from(&b"Break"[..]).unwrap(),
+            StylePart::FillInBA
+        );
+        assert_eq!(<&str>::from(StylePart::FillInBA), "Fill In BA");
+    }
+
+    #[test]
+    fn cseg_roles() {
+        let sff1 = StyleFile::parse(include_bytes!("../test-asset/sff1.prs")).unwrap();
+        let cseg = &sff1.casm.as_ref().unwrap().csegs()[0];
+        let roles = cseg
+            .roles()
+            .into_iter()
+            .map(|(role, ctab)| (role, ctab.name()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            roles,
+            [
+                (AccompanimentPart::Rhythm, "Drs"),
+                (AccompanimentPart::Bass, "Bass"),
+                (AccompanimentPart::Chord1, "Pf L 1"),
+                (AccompanimentPart::Chord2, "Pf R 1"),
+            ]
+        );
+        assert_eq!(AccompanimentPart::Bass.channel(), u4::new(10));
+    }
+
+    #[test]
+    fn ctab_channels() {
+        let drums = Ctab::read(Chunk::Ctab1(CTAB1_DRUMS)).unwrap();
+        assert_eq!(drums.source_channel_u8(), 9);
+        assert_eq!(drums.source_channel_number(), 10);
+        let chord = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        assert_eq!(chord.dest_channel_u8(), 11);
+        assert_eq!(chord.dest_channel_number(), 12);
+    }
+
+    #[test]
+    fn ctab_source_role() {
+        // Source on Ch11
+        let mut raw = CTAB1_CHORD.to_vec();
+        raw[0] = 0x0A;
+        let bass = Ctab::read(Chunk::Ctab1(&raw)).unwrap();
+        assert_eq!(bass.source_role(), Some(AccompanimentPart::Bass));
+        // Source on Ch1
+        let chord = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        assert_eq!(chord.source_role(), None);
+        assert_eq!(chord.dest_role(), Some(AccompanimentPart::Chord1));
+    }
+
+    #[test]
+    fn scan_counts() {
+        let raw = include_bytes!("../test-asset/sff1.prs");
+        let counts = StyleFile::scan_counts(raw).unwrap();
+        let sff1 = StyleFile::parse(raw).unwrap();
+        let csegs = sff1.casm.as_ref().unwrap().csegs();
+        assert_eq!(counts.casm, 1);
+        assert_eq!(counts.cseg, 3);
+        assert_eq!(counts.cseg, csegs.len());
+        assert_eq!(
+            counts.ctab,
+            csegs.iter().map(|cseg| cseg.ctabs().len()).sum::<usize>()
+        );
+        assert_eq!(counts.record, 6);
+        assert_eq!(counts.ots, 1);
+    }
+
+    /// Wrap `data` in a chunk with the given id.
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        chunk.extend_from_slice(data);
+        chunk
+    }
+
+    /// A minimal style file, with an empty track and a single CSEG holding the given SFFv1 CTABs.
+    fn style_bytes(ctabs: &[&[u8]]) -> Vec<u8> {
+        let mut cseg = chunk(b"Sdec", b"Main A");
+        for ctab in ctabs {
+            cseg.extend(chunk(b"Ctab", ctab));
+        }
+        let mut raw = chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]);
+        raw.extend(chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]));
+        raw.extend(chunk(b"CASM", &chunk(b"CSEG", &cseg)));
+        raw
+    }
+
+    #[test]
+    fn parse_lenient() {
+        let (sff2, diagnostics) =
+            StyleFile::parse_lenient(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(sff2.mdb.unwrap().records().len(), 1);
+
+        // The second record is too short to hold a tempo and a signature
+        let records = [
+            chunk(b"FNRP", &[0x07, 0xA1, 0x20, 0x04, 0x04]),
+            chunk(b"FNRP", &[0x07, 0xA1]),
+        ]
+        .concat();
+        let mut raw = style_bytes(&[CTAB1_CHORD]);
+        raw.extend(chunk(b"FNRc", &records));
+        if cfg!(feature = "strict") {
+            assert!(StyleFile::parse(&raw).is_err());
+        }
+        let (style, diagnostics) = StyleFile::parse_lenient(&raw).unwrap();
+        assert_eq!(style.mdb.unwrap().records().len(), 1);
+        assert_eq!(style.casm.unwrap().csegs()[0].ctabs().len(), 1);
+        assert_eq!(style.tracks.len(), 1);
+        assert_eq!(diagnostics.len(), 1);
+        let Diagnostic { section, error } = &diagnostics[0];
+        assert_eq!(section, b"FNRc");
+        assert_eq!(error.kind().message(), "invalid Record");
+
+        // A bad record only drops itself
+        let records = [
+            chunk(b"FNRP", &[0x07, 0xA1]),
+            chunk(b"FNRP", &[0x07, 0xA1, 0x20, 0x04, 0x04]),
+            chunk(b"FNRP", &[0x07, 0xA1, 0x20, 0x03, 0x04]),
+        ]
+        .concat();
+        let mut raw = style_bytes(&[CTAB1_CHORD]);
+        raw.extend(chunk(b"FNRc", &records));
+        let (style, diagnostics) = StyleFile::parse_lenient(&raw).unwrap();
+        assert_eq!(style.mdb.unwrap().records().len(), 2);
+        assert_eq!(diagnostics.len(), 1);
+
+        assert!(StyleFile::parse_lenient(b"RIFF").is_err());
+    }
+
+    #[test]
+    fn parse_lenient_keeps_ctabs() {
+        // A set note mute nibble, and an unknown transposition table with a short trailer
+        let mut mute = CTAB1_CHORD.to_vec();
+        mute[11] = 0x1F;
+        let mut ntt = CTAB2_BASS.to_vec();
+        ntt[23] = 0x0F;
+        ntt.truncate(ntt.len() - 3);
+        let cseg = [
+            chunk(b"Sdec", b"Main A"),
+            chunk(b"Ctab", &mute),
+            chunk(b"Ctb2", &ntt),
+        ]
+        .concat();
+        let raw = [
+            chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]),
+            chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]),
+            chunk(b"CASM", &chunk(b"CSEG", &cseg)),
+        ]
+        .concat();
+        if cfg!(feature = "strict") {
+            assert!(StyleFile::parse(&raw).is_err());
+        }
+        let (style, diagnostics) = StyleFile::parse_lenient(&raw).unwrap();
+        let messages = diagnostics
+            .iter()
+            .map(|diag| diag.error.kind().message())
+            .collect::<Vec<_>>();
+        assert!(messages.contains(&"note mute first nibble is not 0"));
+        assert!(messages.contains(&"unknown transposition table"));
+        let ctabs = style.casm.unwrap().csegs()[0].ctabs().to_vec();
+        assert_eq!(ctabs.len(), 2);
+        assert_eq!(ctabs[1].tables()[0].ntt(), TranspositionTable::Bypass);
+        assert!(!ctabs[1].trailer_present());
+    }
+
+    #[test]
+    fn to_bytes_roundtrip() {
+        // A style in the form the writers produce
+        let track = [
+            &[0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20][..],
+            &[0x00, 0xFF, 0x06, 0x06],
+            b"Main A",
+            &[0x00, 0x90, 0x3C, 0x40, 0x60, 0x80, 0x3C, 0x00],
+            &[0x00, 0xFF, 0x2F, 0x00],
+        ]
+        .concat();
+        let cseg = [
+            chunk(b"Sdec", b"Main A, Fill In BA"),
+            chunk(b"Ctab", CTAB1_DRUMS),
+            chunk(b"Ctab", CTAB1_CHORD),
+        ]
+        .concat();
+        let record = [
+            &[0x07, 0xA1, 0x20, 0x04, 0x04][..],
+            &chunk(b"Mnam", b"Title"),
+            &chunk(b"Gnam", b"Pop"),
+        ]
+        .concat();
+        let raw = [
+            chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]),
+            chunk(b"MTrk", &track),
+            chunk(b"CASM", &chunk(b"CSEG", &cseg)),
+            chunk(b"OTSc", &chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00])),
+            chunk(b"FNRc", &chunk(b"FNRP", &record)),
+            chunk(b"MHhd", &[0x00, 0x00, 0x00, 0x01]),
+        ]
+        .concat();
+        let mut style = StyleFile::parse(&raw).unwrap();
+        assert_eq!(style.to_bytes().unwrap(), raw);
+
+        // Edits are written out
+        style.rename("Renamed").unwrap();
+        let out = style.to_bytes().unwrap();
+        let renamed = StyleFile::parse(&out).unwrap();
+        assert_eq!(renamed.mdb.unwrap().records()[0].title(), "Renamed");
+
+        // Real files are not byte identical, but their content survives
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let out = sff2.to_bytes().unwrap();
+        let again = StyleFile::parse(&out).unwrap();
+        assert_eq!(again.tracks, sff2.tracks);
+        assert_eq!(format!("{:?}", again.casm), format!("{:?}", sff2.casm));
+        assert_eq!(again.mdb.unwrap().records(), sff2.mdb.unwrap().records());
+    }
+
+    #[test]
+    fn fingerprint() {
+        let mut sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let fingerprint = sff2.fingerprint();
+        assert_eq!(sff2.fingerprint(), fingerprint);
+        sff2.rename("Renamed").unwrap();
+        assert_eq!(sff2.fingerprint(), fingerprint);
+        let sff1 = StyleFile::parse(include_bytes!("../test-asset/sff1.prs")).unwrap();
+        assert_ne!(sff1.fingerprint(), fingerprint);
+
+        let fingerprint = |ctab: &[u8]| {
+            StyleFile::parse(&style_bytes(&[CTAB1_DRUMS, ctab]))
+                .unwrap()
+                .fingerprint()
+        };
+        let original = fingerprint(CTAB1_CHORD);
+        // The table name does not matter for playback
+        let mut ctab = CTAB1_CHORD.to_vec();
+        ctab[2] = b'X';
+        assert_eq!(fingerprint(&ctab), original);
+        // The note range does
+        let mut ctab = CTAB1_CHORD.to_vec();
+        ctab[24] = 0x60;
+        assert_ne!(fingerprint(&ctab), original);
+
+        // So do the track events
+        let raw = style_bytes(&[CTAB1_DRUMS, CTAB1_CHORD]);
+        let mut style = StyleFile::parse(&raw).unwrap();
+        style.tracks[0].insert(
+            0,
+            TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::Marker(b"Main A")),
+            },
+        );
+        assert_ne!(style.fingerprint(), original);
+        // But not the track names, where the style name is
+        let mut style = StyleFile::parse(&raw).unwrap();
+        style.tracks[0].insert(
+            0,
+            TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::TrackName(b"Renamed")),
+            },
+        );
+        assert_eq!(style.fingerprint(), original);
+        // The value is the same on every platform
+        assert_eq!(original, 0x0AF1_B26D_AE14_0DEC);
+    }
+
+    #[test]
+    fn rename_style() {
+        let reread = |style: &StyleFile| {
+            let mut out = Vec::new();
+            style.mdb.as_ref().unwrap().write(&mut out).unwrap();
+            let mdb = Mdb::parse(ChunkIter::new(&out), &ParseOptions::default())
+                .unwrap()
+                .unwrap();
+            mdb.records()[0].clone()
+        };
+
+        let mut sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let original = sff2.mdb.as_ref().unwrap().records()[0].clone();
+        assert!(!sff2.is_dirty());
+        sff2.rename("Renamed").unwrap();
+        assert!(sff2.is_dirty());
+        let record = reread(&sff2);
+        assert_eq!(record.title(), "Renamed");
+        assert_eq!(record.genre(), original.genre());
+        assert_eq!(record.tempo(), original.tempo());
+
+        // A minimal section is created for styles without one
+        let raw = style_bytes(&[CTAB1_CHORD]);
+        let mut style = StyleFile::parse(&raw).unwrap();
+        assert!(style.mdb.is_none());
+        style.rename("New style").unwrap();
+        let record = reread(&style);
+        assert_eq!(record.title(), "New style");
+        assert_eq!(record.tempo(), u24::new(500_000));
+        assert_eq!(record.signature().numerator(), 4);
+        assert_eq!(record.signature().denominator(), 4);
+
+        // Titles the record encoding cannot represent are rejected
+        style.rename("さくら").unwrap();
+        let mut out = Vec::new();
+        reread(&style)
+            .write_with_encoding(Encoding::ShiftJis, &mut out)
+            .unwrap();
+        let sjis = RecordIter::new(&out).next().unwrap().unwrap();
+        assert_eq!(sjis.encoding(), Encoding::ShiftJis);
+        style.mdb.as_mut().unwrap().records_mut()[0] = sjis;
+        assert!(style.rename("♫ 🎹").is_err());
+        assert_eq!(reread(&style).title(), "さくら");
+    }
+
+    #[test]
+    fn casm_print_tree() {
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let mut tree = String::new();
+        sff2.casm.unwrap().print_tree(&mut tree).unwrap();
+        assert!(tree.starts_with("CASM\n  CSEG 0: Main A, Main B, Main C, Fill In AA, Intro A\n"));
+        assert!(tree.contains(concat!(
+            "    CTAB 3 \"E.Bass\": Ch11 -> Ch11 (Bass)\n",
+            "      Table 0: RootTransposition, Melody, high key E♭, notes E1..G9, bass on\n",
+        )));
+        assert!(tree.contains("  CSEG 1: Main D, Fill In CC, Fill In DD, Ending A, Fill In BA\n"));
+    }
+
+    #[test]
+    fn casm_build_roundtrip() {
+        let same = |a: &Cseg, b: &Cseg| {
+            assert_eq!(a.style_parts(), b.style_parts());
+            assert_eq!(format!("{:?}", a.ctabs()), format!("{:?}", b.ctabs()));
+        };
+        let rebuild = |csegs: Vec<OwnedCseg>| {
+            let raw = Casm::build(csegs).unwrap();
+            Casm::parse(ChunkIter::new(&raw), &ParseOptions::default())
+                .unwrap()
+                .unwrap()
+                .csegs()
+                .iter()
+                .cloned()
+                .map(Cseg::into_owned)
+                .collect::<Vec<_>>()
+        };
+
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let csegs = sff2.casm.as_ref().unwrap().csegs();
+        let rebuilt = rebuild(csegs.iter().cloned().map(Cseg::into_owned).collect());
+        assert_eq!(rebuilt.len(), csegs.len());
+        for (cseg, again) in csegs.iter().zip(&rebuilt) {
+            same(cseg, again);
+        }
+
+        // A segment built from scratch
+        let ctabs = vec![
+            Ctab::read(Chunk::Ctab1(CTAB1_DRUMS)).unwrap().into_owned(),
+            Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap().into_owned(),
+        ];
+        let cseg = Cseg::new(vec![StylePart::MainA, StylePart::FillInBA], ctabs);
+        assert_eq!(cseg.sdec_raw(), vec!["Main A", "Fill In BA"]);
+        let raw = Casm::build(vec![cseg.clone()]).unwrap();
+        let sdec = chunk(b"Sdec", b"Main A, Fill In BA");
+        assert!(raw.windows(sdec.len()).any(|window| window == &sdec[..]));
+        let rebuilt = rebuild(vec![cseg.clone()]);
+        assert_eq!(rebuilt.len(), 1);
+        same(&cseg, &rebuilt[0]);
+    }
+
+    #[test]
+    fn sff_version() {
+        let sff1 = StyleFile::parse(include_bytes!("../test-asset/sff1.prs")).unwrap();
+        assert_eq!(sff1.sff_version(), Some(SffVersion::V1));
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        assert_eq!(sff2.sff_version(), Some(SffVersion::V2));
+
+        // Without marker, the version is inferred from the tables
+        let style = |cseg: &[u8], extra: &[u8]| {
+            let mut raw = chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]);
+            raw.extend(chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]));
+            raw.extend(chunk(b"CASM", &chunk(b"CSEG", cseg)));
+            raw.extend(extra);
+            raw
+        };
+        let sdec = chunk(b"Sdec", b"Main A");
+        let raw = style(&[&sdec[..], &chunk(b"Ctb2", CTAB2_BASS)].concat(), &[]);
+        let v2 = StyleFile::parse(&raw).unwrap();
+        assert!(v2.mh.is_none());
+        assert_eq!(v2.sff_version(), Some(SffVersion::V2));
+        let raw = style(&[&sdec[..], &chunk(b"Ctab", CTAB1_CHORD)].concat(), &[]);
+        assert_eq!(
+            StyleFile::parse(&raw).unwrap().sff_version(),
+            Some(SffVersion::V1)
+        );
+        let raw = style(&sdec, &[]);
+        assert_eq!(StyleFile::parse(&raw).unwrap().sff_version(), None);
+        // A MH section tells nothing about the version
+        let raw = style(&sdec, &chunk(b"MHhd", &[0x00]));
+        assert_eq!(StyleFile::parse(&raw).unwrap().sff_version(), None);
+    }
+
+    #[test]
+    fn detect_sff_version() {
+        let sff1 = include_bytes!("../test-asset/sff1.prs");
+        assert_eq!(crate::detect_sff_version(sff1).unwrap(), SffVersion::V1);
+        let sff2 = include_bytes!("../test-asset/sff2.prs");
+        assert_eq!(crate::detect_sff_version(sff2).unwrap(), SffVersion::V2);
+
+        // Same inference as `StyleFile::sff_version` without a marker
+        let raw = style_bytes(&[CTAB1_CHORD]);
+        assert_eq!(crate::detect_sff_version(&raw).unwrap(), SffVersion::V1);
+        let mut raw = chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]);
+        raw.extend(chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]));
+        let cseg = [chunk(b"Sdec", b"Main A"), chunk(b"Ctb2", CTAB2_BASS)].concat();
+        raw.extend(chunk(b"CASM", &chunk(b"CSEG", &cseg)));
+        assert_eq!(crate::detect_sff_version(&raw).unwrap(), SffVersion::V2);
+
+        assert!(crate::detect_sff_version(&style_bytes(&[])).is_err());
+        let mut raw = style_bytes(&[]);
+        raw.extend(chunk(b"MHhd", &[0x00]));
+        assert!(crate::detect_sff_version(&raw).is_err());
+        assert!(crate::detect_sff_version(b"RIFF").is_err());
+    }
+
+    #[test]
+    fn psr2000_parts() {
+        // PSR-2000 styles add an Intro D and an Ending D
+        let marker =
+            |delta: u8, name: &[u8]| [&[delta, 0xFF, 0x06, name.len() as u8][..], name].concat();
+        let track = [
+            marker(0x00, b"SInt"),
+            marker(0x60, b"Intro D"),
+            marker(0x60, b"Ending D"),
+            vec![0x60, 0xFF, 0x2F, 0x00],
+        ]
+        .concat();
+        let cseg = [
+            chunk(b"Sdec", b"Intro D,Ending D"),
+            chunk(b"Ctab", CTAB1_CHORD),
+        ]
+        .concat();
+        let raw = [
+            chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]),
+            chunk(b"MTrk", &track),
+            chunk(b"CASM", &chunk(b"CSEG", &cseg)),
+        ]
+        .concat();
+        let style = StyleFile::parse(&raw).unwrap();
+        let cseg = &style.casm.as_ref().unwrap().csegs()[0];
+        assert_eq!(cseg.style_parts(), &[StylePart::IntroD, StylePart::EndingD]);
+        assert!(cseg.validate().is_empty());
+        assert_eq!(
+            style.parts_bitmask(),
+            1 << StylePart::IntroD.index() | 1 << StylePart::EndingD.index()
+        );
+        assert_eq!(
+            style.part_ticks(),
+            vec![
+                (StylePart::IntroD, 0x60..0xC0),
+                (StylePart::EndingD, 0xC0..0x120)
+            ]
+        );
+
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let parts = sff2.part_ticks();
+        assert_eq!(parts.len(), 15);
+        assert_eq!(parts[0], (StylePart::MainA, 7680..69120));
+    }
+
+    #[test]
+    fn key_chord_from_str() {
+        assert_eq!("Bb".parse::<Key>().unwrap(), Key::Bb);
+        assert_eq!("C".parse::<Key>().unwrap(), Key::C);
+        assert_eq!("C#".parse::<Key>().unwrap(), Key::Cs);
+        assert_eq!("Db".parse::<Key>().unwrap(), Key::Cs);
+        assert_eq!("Cb".parse::<Key>().unwrap(), Key::B);
+        assert_eq!("E♭".parse::<Key>().unwrap(), Key::Eb);
+        for key in (0..12).map(|k| Key::try_from(k).unwrap()) {
+            assert_eq!(key.to_string().parse::<Key>().unwrap(), key);
+        }
+        assert!("H".parse::<Key>().is_err());
+        assert!("C##".parse::<Key>().is_err());
+        assert!("".parse::<Key>().is_err());
+
+        assert_eq!("m7b5".parse::<Chord>().unwrap(), Chord::Min7b5);
+        assert_eq!("m7♭5".parse::<Chord>().unwrap(), Chord::Min7b5);
+        assert_eq!("7(#9)".parse::<Chord>().unwrap(), Chord::SevenS9);
+        assert_eq!("".parse::<Chord>().unwrap(), Chord::Maj);
+        assert!("m13".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn chord_third() {
+        assert_eq!(Chord::Maj.third(), Some(Third::Major));
+        assert_eq!(Chord::Min.third(), Some(Third::Minor));
+        assert_eq!(Chord::Sus4.third(), None);
+        assert_eq!(Chord::OnePlusFive.third(), None);
+        assert_eq!(Chord::SevenS9.third(), Some(Third::Major));
+        assert_eq!(Chord::Cancel.third(), None);
+    }
+
+    #[test]
+    fn partial_chords() {
+        assert_eq!(Chord::OnePlusEight.intervals(), &[0, 12]);
+        assert_eq!(Chord::OnePlusFive.intervals(), &[0, 7]);
+        assert_eq!(Chord::OnePlusTwoPlus5.intervals(), &[0, 2, 7]);
+        let partial: Vec<Chord> = Chord::ALL
+            .iter()
+            .copied()
+            .filter(Chord::is_partial)
+            .collect();
+        assert_eq!(
+            partial,
+            vec![
+                Chord::OnePlusEight,
+                Chord::OnePlusFive,
+                Chord::OnePlusTwoPlus5
+            ]
+        );
+        for chord in partial {
+            assert_eq!(chord.third(), None);
+            assert!(chord.is_chord_type());
+        }
+        assert!(!Chord::Sus4.is_partial());
+    }
+
+    #[test]
+    fn chord_describe() {
+        assert_eq!(
+            Chord::Min7b5.describe(),
+            "Minor seventh flat five (half-diminished): root, ♭3, ♭5, ♭7\nSymbol: Cm7♭5"
+        );
+        assert_eq!(
+            Chord::Dim7.describe(),
+            "Diminished seventh: root, ♭3, ♭5, 𝄫7\nSymbol: Cdim7"
+        );
+        assert_eq!(Chord::Cancel.describe(), "Chord cancel: no chord is played");
+        for chord in Chord::ALL.iter() {
+            assert!(!chord.describe().contains('?'));
+            assert_eq!(chord.intervals().is_empty(), !chord.is_chord_type());
+        }
+    }
+
+    #[test]
+    fn chord_symbol_suffix() {
+        assert_eq!(Chord::Min7.symbol_suffix(), "m7");
+        assert_eq!(Chord::Maj.symbol_suffix(), "");
+        assert_eq!(Chord::Maj7.symbol_suffix(), "M7");
+        assert_eq!(
+            format!("{}{}", Key::Fs, Chord::Dim.symbol_suffix()),
+            "F♯dim"
+        );
+        for chord in Chord::ALL.iter().filter(|chord| chord.is_chord_type()) {
+            assert_eq!(chord.symbol_suffix().parse::<Chord>().unwrap(), *chord);
+        }
+    }
+
+    #[test]
+    fn key_spelling() {
+        assert_eq!(Key::Eb.letter(), 'E');
+        assert_eq!(Key::Eb.accidental(), Some(Accidental::Flat));
+        assert_eq!(Key::Cs.letter(), 'C');
+        assert_eq!(Key::Cs.accidental(), Some(Accidental::Sharp));
+        assert_eq!(Key::A.accidental(), None);
+        assert_eq!(char::from(Key::Bb), 'B');
+    }
+
+    #[test]
+    fn parse_metadata_only() {
+        let raw = include_bytes!("../test-asset/sff2.prs");
+        let full = StyleFile::parse(raw).unwrap();
+        let meta = StyleFile::parse_metadata_only(raw).unwrap();
+        assert!(meta.tracks.is_empty());
+        assert!(meta.casm.is_none());
+        assert!(meta.ots.is_none());
+        assert_eq!(meta.header, full.header);
+        assert_eq!(meta.mh.is_some(), full.mh.is_some());
+        let titles = |style: &StyleFile| -> Vec<String> {
+            style
+                .mdb
+                .as_ref()
+                .unwrap()
+                .records()
+                .iter()
+                .map(|r| r.title().to_string())
+                .collect()
+        };
+        assert_eq!(titles(&meta), titles(&full));
+
+        assert!(StyleFile::parse_metadata_only(b"RIFF").is_err());
+    }
+
+    #[test]
+    fn reference_key() {
+        let with_source_chord = |key: Key| {
+            let mut ctab = CTAB1_CHORD.to_vec();
+            ctab[18] = key as u8;
+            ctab
+        };
+        let (c, g) = (with_source_chord(Key::C), with_source_chord(Key::G));
+        let raw = style_bytes(&[&g, &c, &c, &g, &c]);
+        let style = StyleFile::parse(&raw).unwrap();
+        assert_eq!(style.casm.unwrap().reference_key(), Some(Key::C));
+        // Ties go to the first key
+        let raw = style_bytes(&[&g, &c]);
+        let style = StyleFile::parse(&raw).unwrap();
+        assert_eq!(style.casm.unwrap().reference_key(), Some(Key::G));
+        let raw = style_bytes(&[]);
+        let style = StyleFile::parse(&raw).unwrap();
+        assert_eq!(style.casm.unwrap().reference_key(), None);
+    }
+
+    #[test]
+    fn part_channels() {
+        let cseg = [
+            chunk(b"Sdec", b"Main A,Fill In AA"),
+            chunk(b"Ctab", CTAB1_DRUMS),
+            chunk(b"Ctb2", CTAB2_BASS),
+            chunk(b"Ctab", CTAB1_DRUMS),
+        ]
+        .concat();
+        let raw = [
+            chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]),
+            chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]),
+            chunk(b"CASM", &chunk(b"CSEG", &cseg)),
+        ]
+        .concat();
+        let style = StyleFile::parse(&raw).unwrap();
+        assert_eq!(style.part_channels(StylePart::MainA), vec![10, 11]);
+        assert!(style.part_channels(StylePart::MainB).is_empty());
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        for &part in StyleCategory::Main.parts() {
+            assert!(sff2
+                .part_channels(part)
+                .iter()
+                .all(|&ch| (9..=16).contains(&ch)));
+        }
+    }
+
+    #[test]
+    fn find_ctab() {
+        let drums = Ctab::read(Chunk::Ctab1(CTAB1_DRUMS)).unwrap();
+        let chord = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        let bass = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        let casm = Casm::new(vec![
+            Cseg::new(vec![StylePart::MainA], vec![drums, chord.clone()]),
+            Cseg::new(vec![StylePart::MainB], vec![bass, chord]),
+        ]);
+        let found = casm.find_ctab(|ctab| ctab.name() == "E.Bass").unwrap();
+        assert_eq!(found.dest_channel_number(), 11);
+        // The first match wins
+        let found = casm
+            .find_ctab(|ctab| ctab.name().starts_with("Pf"))
+            .unwrap();
+        assert!(std::ptr::eq(found, &casm.csegs()[0].ctabs()[1]));
+        assert!(casm.find_ctab(|ctab| ctab.name() == "Guitar").is_none());
+    }
+
+    #[test]
+    fn sdec_raw() {
+        let cseg = [
+            chunk(b"Sdec", b"Main A, Main Z"),
+            chunk(b"Ctab", CTAB1_CHORD),
+        ]
+        .concat();
+        let raw = [
+            chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]),
+            chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]),
+            chunk(b"CASM", &chunk(b"CSEG", &cseg)),
+        ]
+        .concat();
+        let style = StyleFile::parse(&raw);
+        if cfg!(feature = "strict") {
+            assert!(style.is_err());
+            return;
+        }
+        let style = style.unwrap();
+        let cseg = &style.casm.as_ref().unwrap().csegs()[0];
+        assert_eq!(cseg.sdec_raw(), vec!["Main A", "Main Z"]);
+        assert_eq!(cseg.style_parts(), &[StylePart::MainA]);
+        // The labels are written back as read
+        assert_eq!(style.to_bytes().unwrap(), raw);
+    }
+
+    #[test]
+    fn sdec_spaces() {
+        let cseg = [
+            chunk(b"Sdec", b"Intro A, Main A, Ending A"),
+            chunk(b"Ctab", CTAB1_CHORD),
+        ]
+        .concat();
+        let raw = [
+            chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]),
+            chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]),
+            chunk(b"CASM", &chunk(b"CSEG", &cseg)),
+        ]
+        .concat();
+        let style = StyleFile::parse(&raw).unwrap();
+        let cseg = &style.casm.as_ref().unwrap().csegs()[0];
+        assert_eq!(
+            cseg.style_parts(),
+            &[StylePart::IntroA, StylePart::MainA, StylePart::EndingA]
+        );
+    }
+
+    #[test]
+    fn cseg_validate() {
+        let raw = style_bytes(&[CTAB1_DRUMS, CTAB1_CHORD, CTAB1_CHORD]);
+        let style = StyleFile::parse(&raw).unwrap();
+        let cseg = &style.casm.as_ref().unwrap().csegs()[0];
+        assert_eq!(
+            cseg.validate(),
+            vec![CsegWarning::DuplicateDest {
+                dest: cseg.ctabs()[1].dest(),
+                first: 1,
+                second: 2,
+            }]
+        );
+
+        let raw = style_bytes(&[]);
+        let style = StyleFile::parse(&raw).unwrap();
+        let cseg = &style.casm.as_ref().unwrap().csegs()[0];
+        assert_eq!(cseg.validate(), vec![CsegWarning::NoCtab]);
+
+        for bytes in &[
+            &include_bytes!("../test-asset/sff1.prs")[..],
+            &include_bytes!("../test-asset/sff2.prs")[..],
+        ] {
+            let style = StyleFile::parse(bytes).unwrap();
+            for cseg in style.casm.as_ref().unwrap().csegs() {
+                assert_eq!(cseg.validate(), vec![]);
+            }
+        }
+    }
+
+    #[test]
+    fn supported_chords() {
+        let raw = style_bytes(&[CTAB1_CHORD]);
+        let style = StyleFile::parse(&raw).unwrap();
+        let chords = style.supported_chords();
+        assert_eq!(chords.len(), 25);
+        assert!(chords.contains(&Chord::Maj));
+        assert!(chords.contains(&Chord::Sus4));
+        assert!(!chords.contains(&Chord::OnePlusEight));
+        assert!(!chords.contains(&Chord::SevenB9));
+        assert!(!chords.contains(&Chord::Cancel));
+
+        // Every chord is played by at least one table of sff1.
+        let sff1 = StyleFile::parse(include_bytes!("../test-asset/sff1.prs")).unwrap();
+        assert_eq!(sff1.supported_chords().len(), 34);
+    }
+
+    #[test]
+    fn upgrade_to_v2() {
+        let v1 = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        let v2 = v1.upgrade_to_v2();
+        assert_eq!(v2.version(), Version::Ctab2);
+        assert_eq!(v2.tables().len(), 3);
+        for table in v2.tables() {
+            assert_eq!(table, &v1.tables()[0]);
+        }
+        assert_eq!(v2.range(), (u7::new(0), u7::new(127)));
+        assert_eq!(v2.special().map(<[u8]>::len), Some(7));
+        assert_eq!(v2.name(), v1.name());
+        for chord in Chord::ALL.iter().copied() {
+            assert_eq!(v2.is_chord_muted(chord), v1.is_chord_muted(chord));
+        }
+        // Upgrading twice does nothing
+        assert_eq!(v2.upgrade_to_v2().tables(), v2.tables());
+
+        // The SFFv1 bass table
+        let mut raw = CTAB1_CHORD.to_vec();
+        raw[21] = 0x03;
+        let v1 = Ctab::read(Chunk::Ctab1(&raw)).unwrap();
+        assert_eq!(v1.tables()[0].ntt(), TranspositionTable::Bass);
+        let v2 = v1.upgrade_to_v2();
+        for table in v2.tables() {
+            assert_eq!(table.ntt(), TranspositionTable::Melody);
+        }
+    }
+
+    #[test]
+    fn ctab_truncated() {
+        // Every field of a CTABv1 up to the special flag is mandatory.
+        for len in 0..CTAB1_CHORD.len() {
+            assert!(Ctab::read(Chunk::Ctab1(&CTAB1_CHORD[..len])).is_err());
+        }
+        assert!(Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).is_ok());
+
+        // The transposition tables and the special bytes at the end of a CTABv2 are only
+        // required in strict mode.
+        let tables_start = 22;
+        let special_start = CTAB2_BASS.len() - 7;
+        for len in 0..tables_start {
+            assert!(Ctab::read(Chunk::Ctab2(&CTAB2_BASS[..len])).is_err());
+        }
+        for len in tables_start..special_start {
+            let ctab = Ctab::read(Chunk::Ctab2(&CTAB2_BASS[..len]));
+            if cfg!(feature = "strict") {
+                assert!(ctab.is_err());
+            } else {
+                assert_eq!(ctab.unwrap().tables().len(), 3);
+            }
+        }
+        for len in special_start..CTAB2_BASS.len() {
+            let ctab = Ctab::read(Chunk::Ctab2(&CTAB2_BASS[..len]));
+            if cfg!(feature = "strict") {
+                assert!(ctab.is_err());
+            } else {
+                assert_eq!(ctab.unwrap().special(), None);
+            }
+        }
+        let ctab = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        assert_eq!(ctab.special(), Some(&CTAB2_BASS[special_start..]));
+    }
+
+    #[test]
+    fn ctab_missing_table() {
+        // The high table is cut off, along with the special bytes
+        let raw = &CTAB2_BASS[..22 + 2 * 6];
+        let skipped = RefCell::new(Vec::new());
+        let on_skip = |err: &Error| skipped.borrow_mut().push(err.kind().message());
+        let options = ParseOptions {
+            on_skip: Some(&on_skip),
+            ..ParseOptions::default()
+        };
+        let ctab = Ctab::read_with(Chunk::Ctab2(raw), &options);
+        if cfg!(feature = "strict") {
+            assert!(ctab.is_err());
+            return;
+        }
+        let ctab = ctab.unwrap();
+        let full = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        assert_eq!(ctab.tables()[..2], full.tables()[..2]);
+        assert_eq!(ctab.tables()[2], Table::default());
+        assert!(!ctab.trailer_present());
+        assert!(skipped.borrow().contains(&"missing transposition tables"));
+    }
+
+    #[test]
+    fn into_ctabs() {
+        let ctabs = {
+            let raw = include_bytes!("../test-asset/sff2.prs").to_vec();
+            let style = StyleFile::parse(&raw).unwrap();
+            let names = style
+                .ctabs()
+                .map(|c| c.name().to_string())
+                .collect::<Vec<_>>();
+            let ctabs = style.into_ctabs();
+            assert_eq!(ctabs.iter().map(Ctab::name).collect::<Vec<_>>(), names);
+            ctabs
+        };
+        // The tables outlive the parsed bytes
+        assert_eq!(ctabs.len(), 64);
+        assert!(ctabs
+            .iter()
+            .all(|c| c.special().map(<[u8]>::len) == Some(7)));
+    }
+
+    #[test]
+    fn parse_on_skip() {
+        let mut malformed = CTAB1_CHORD.to_vec();
+        malformed[18] = 0x20;
+        let raw = style_bytes(&[CTAB1_DRUMS, &malformed]);
+        let skipped = RefCell::new(Vec::new());
+        let on_skip = |err: &Error| skipped.borrow_mut().push(err.kind().message());
+        let options = ParseOptions {
+            on_skip: Some(&on_skip),
+            ..ParseOptions::default()
+        };
+
+        // Defaulted and accepted values are reported too
+        let lenient = ParseOptions {
+            lenient: true,
+            ..options
+        };
+        let mut name = CTAB1_CHORD.to_vec();
+        name[1] = 0xFF;
+        let ctab = Ctab::read_with(Chunk::Ctab1(&name), &lenient).unwrap();
+        assert_eq!(ctab.name(), "");
+        let four = [&CTAB2_BASS[..40], &CTAB2_BASS[34..40], &CTAB2_BASS[40..]].concat();
+        assert!(Ctab::read_with(Chunk::Ctab2(&four), &lenient).is_ok());
+        // A lone Shift-JIS lead byte
+        let record = [
+            &[0x07, 0xA1, 0x20, 0x04, 0x04][..],
+            &chunk(b"Mnam", &[0x82, 0x20]),
+        ]
+        .concat();
+        let record = Record::read_with(Chunk::Record(&record), &lenient).unwrap();
+        assert_eq!(record.title(), "");
+        assert_eq!(
+            skipped.replace(Vec::new()),
+            [
+                "not a valid string for name",
+                "unexpected number of transposition tables",
+                "text chunk is not valid Shift-JIS",
+            ]
+        );
+
+        let style = StyleFile::parse_with(&raw, options);
+        if cfg!(feature = "strict") {
+            assert!(style.is_err());
+            assert!(skipped.borrow().is_empty());
+            return;
+        }
+        assert!(style.unwrap().casm.unwrap().csegs().is_empty());
+        assert_eq!(*skipped.borrow(), ["invalid CSEG"]);
+
+        // The happy path is left alone
+        skipped.borrow_mut().clear();
+        let raw = style_bytes(&[CTAB1_DRUMS, CTAB1_CHORD]);
+        assert!(StyleFile::parse_with(&raw, options).is_ok());
+        assert!(skipped.borrow().is_empty());
+    }
+
+    #[test]
+    fn has_bass_table() {
+        let mut raw = CTAB2_BASS.to_vec();
+        // Bass mode is the top bit of the transposition table byte of the low, mid and high tables
+        for ntt in &[23, 29, 35] {
+            raw[*ntt] &= 0x7F;
+        }
+        let ctab = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        assert!(!ctab.has_bass_table());
+        raw[29] |= 0x80;
+        let ctab = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        let bass = ctab.tables().iter().map(Table::bass_on).collect::<Vec<_>>();
+        assert_eq!(bass, [false, true, false]);
+        assert!(ctab.has_bass_table());
+
+        let v1 = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        assert!(!v1.has_bass_table());
+    }
+
+    #[test]
+    fn chord_alias() {
+        let mut legacy = CTAB1_CHORD.to_vec();
+        legacy[19] = 0x30;
+        let raw = style_bytes(&[&legacy]);
+        let alias = |byte: u8| match byte {
+            0x30 => Some(Chord::Min7),
+            _ => None,
+        };
+        let options = ParseOptions {
+            chord_alias: Some(&alias),
+            ..ParseOptions::default()
+        };
+        let style = StyleFile::parse_with(&raw, options).unwrap();
+        let ctab = style.ctabs().next().unwrap();
+        assert_eq!(ctab.source_chord_type(), Chord::Min7);
+        // Other bytes keep their standard meaning
+        let standard = style_bytes(&[CTAB1_CHORD]);
+        let style = StyleFile::parse_with(&standard, options).unwrap();
+        let ctab = style.ctabs().next().unwrap();
+        assert_eq!(ctab.source_chord_type(), Chord::Maj7);
+
+        // Without the alias, the table cannot be read
+        let style = StyleFile::parse(&raw);
+        assert!(style.map_or(true, |style| style.ctabs().next().is_none()));
+    }
+
+    #[test]
+    fn ctab_trailer_present() {
+        assert!(Ctab::read(Chunk::Ctab2(CTAB2_BASS))
+            .unwrap()
+            .trailer_present());
+        assert!(Ctab::read(Chunk::Ctab1(CTAB1_CHORD))
+            .unwrap()
+            .trailer_present());
+        if cfg!(feature = "strict") {
+            return;
+        }
+        // Truncated right before the special bytes
+        let truncated = &CTAB2_BASS[..CTAB2_BASS.len() - 7];
+        let ctab = Ctab::read(Chunk::Ctab2(truncated)).unwrap();
+        assert!(!ctab.trailer_present());
+
+        let cseg = [chunk(b"Sdec", b"Main A"), chunk(b"Ctb2", truncated)].concat();
+        let raw = [
+            chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]),
+            chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]),
+            chunk(b"CASM", &chunk(b"CSEG", &cseg)),
+        ]
+        .concat();
+        let style = StyleFile::parse(&raw).unwrap();
+        let cseg = &style.casm.as_ref().unwrap().csegs()[0];
+        assert_eq!(
+            cseg.validate(),
+            vec![CsegWarning::MissingTrailer { index: 0 }]
+        );
+    }
+
+    #[test]
+    fn records_across_sections() {
+        let record = |title: &[u8]| {
+            let data = [&[0x07, 0xA1, 0x20, 0x04, 0x04][..], &chunk(b"Mnam", title)].concat();
+            chunk(b"FNRc", &chunk(b"FNRP", &data))
+        };
+        let raw = [
+            style_bytes(&[CTAB1_CHORD]),
+            record(b"First"),
+            record(b"Second"),
+        ]
+        .concat();
+        let style = StyleFile::parse(&raw).unwrap();
+        assert_eq!(style.mdb.as_ref().unwrap().records().len(), 1);
+        let titles: Vec<_> = style.records().map(Record::title).collect();
+        assert_eq!(titles, ["First", "Second"]);
+        let again = style.to_bytes().unwrap();
+        let titles: Vec<_> = StyleFile::parse(&again)
+            .unwrap()
+            .records()
+            .map(|record| record.title().to_string())
+            .collect();
+        assert_eq!(titles, ["First", "Second"]);
+
+        // Malformed sections are an error in strict mode, and are otherwise skipped
+        let bad = chunk(b"FNRc", &chunk(b"FNRP", &[0x07, 0xA1]));
+        let raw = [
+            style_bytes(&[CTAB1_CHORD]),
+            record(b"First"),
+            bad,
+            record(b"Third"),
+        ]
+        .concat();
+        if cfg!(feature = "strict") {
+            assert!(StyleFile::parse(&raw).is_err());
+        } else {
+            let style = StyleFile::parse(&raw).unwrap();
+            let titles: Vec<_> = style.records().map(Record::title).collect();
+            assert_eq!(titles, ["First", "Third"]);
+        }
+        let (style, diagnostics) = StyleFile::parse_lenient(&raw).unwrap();
+        let titles: Vec<_> = style.records().map(Record::title).collect();
+        assert_eq!(titles, ["First", "Third"]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(&diagnostics[0].section, b"FNRc");
+    }
+
+    #[test]
+    fn chunks_ended_cleanly() {
+        let raw = style_bytes(&[CTAB1_CHORD]);
+        let mut chunks = ChunkIter::new(&raw);
+        assert!(!chunks.ended_cleanly());
+        chunks.by_ref().for_each(drop);
+        assert!(chunks.ended_cleanly());
+
+        // Truncated in the middle of the CTAB
+        let truncated = &raw[..raw.len() - 5];
+        let mut chunks = ChunkIter::new(truncated);
+        chunks.by_ref().for_each(drop);
+        assert!(!chunks.ended_cleanly());
+
+        let skipped = RefCell::new(Vec::new());
+        let on_skip = |err: &Error| skipped.borrow_mut().push(err.kind().message());
+        let options = ParseOptions {
+            on_skip: Some(&on_skip),
+            ..ParseOptions::default()
+        };
+        let style = StyleFile::parse_with(truncated, options);
+        if cfg!(feature = "strict") {
+            assert!(style.is_err());
+        } else {
+            assert!(style.is_ok());
+            assert!(skipped.borrow().contains(&"reached eof before chunk ended"));
+        }
+    }
+
+    #[test]
+    fn retain_raw() {
+        let raw = include_bytes!("../test-asset/sff2.prs");
+        let style = StyleFile::parse(raw).unwrap();
+        assert!(style.casm.unwrap().raw().is_none());
+        assert!(style.mdb.unwrap().raw().is_none());
+
+        let options = ParseOptions {
+            retain_raw: true,
+            ..ParseOptions::default()
+        };
+        let style = StyleFile::parse_with(raw, options).unwrap();
+        let sections: Vec<_> = ChunkIter::new(raw).map(Result::unwrap).collect();
+        let casm = sections
+            .iter()
+            .find_map(|c| match c {
+                Chunk::Casm(data) => Some(*data),
+                _ => None,
+            })
+            .unwrap();
+        let mdb = sections
+            .iter()
+            .find_map(|c| match c {
+                Chunk::Mdb(data) => Some(*data),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(style.casm.unwrap().raw(), Some(casm));
+        assert_eq!(style.mdb.unwrap().raw(), Some(mdb));
+    }
+
+    #[test]
+    fn edit_record() {
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let record = sff2.records().next().unwrap();
+        let renamed = record.with_title("Renamed").unwrap();
+        let mut out = Vec::new();
+        renamed.write(&mut out).unwrap();
+        let read = Record::read(Chunk::Record(&out[8..])).unwrap();
+        assert_eq!(read.title(), "Renamed");
+        assert_eq!(read.tempo(), record.tempo());
+        assert_eq!(read.signature(), record.signature());
+        assert_eq!(read.genre(), record.genre());
+        assert_eq!(read.keyword1(), record.keyword1());
+        assert_eq!(read.keyword2(), record.keyword2());
+        assert_ne!(record.title(), "Renamed");
+
+        let edited = read
+            .with_genre("Waltz")
+            .and_then(|r| r.with_keywords(Some("slow"), None))
+            .unwrap();
+        assert_eq!(edited.genre(), "Waltz");
+        assert_eq!(edited.title(), "Renamed");
+        assert_eq!((edited.keyword1(), edited.keyword2()), (Some("slow"), None));
+    }
+
+    #[test]
+    fn clone_sections() {
+        let sff2 = StyleFile::parse(include_bytes!("../test-asset/sff2.prs")).unwrap();
+        let mdb = sff2.mdb.as_ref().unwrap();
+        let mut copy = mdb.clone();
+        copy.records_mut()[0].set_title("Copy").unwrap();
+        assert_ne!(copy.records()[0].title(), mdb.records()[0].title());
+        assert_eq!(copy.records().len(), mdb.records().len());
+
+        let casm = sff2.casm.as_ref().unwrap();
+        let count = casm.clone().into_iter().count();
+        assert_eq!(casm.into_iter().count(), count);
+
+        // The raw iterators restart from where they were cloned
+        let mut out = Vec::new();
+        mdb.write(&mut out).unwrap();
+        let mut records = RecordIter::new(&out[8..]);
+        records.next().unwrap().unwrap();
+        let rest = records.clone().count();
+        assert_eq!(records.count(), rest);
+        assert_eq!(rest, mdb.records().len() - 1);
+        let raw = [
+            chunk(b"CSEG", &chunk(b"Sdec", b"Main A")),
+            chunk(b"CSEG", &chunk(b"Sdec", b"Main B")),
+        ]
+        .concat();
+        let mut csegs = CsegIter::new(&raw);
+        csegs.next().unwrap().unwrap();
+        let copy = csegs.clone().next().unwrap().unwrap();
+        let cseg = csegs.next().unwrap().unwrap();
+        assert_eq!(copy.style_parts(), cseg.style_parts());
+        assert_eq!(cseg.style_parts(), &[StylePart::MainB]);
+    }
+
+    #[test]
+    fn build_style_file() {
+        let ctab = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        let mut record = Record::new(u24::new(500_000), 3, 4);
+        record.set_title("Tiny Waltz").unwrap();
+        let ots = chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]);
+        let bytes = StyleFileBuilder::new()
+            .cseg(Cseg::new(vec![StylePart::MainA], vec![ctab.clone()]))
+            .record(record.clone())
+            .ots(&ots)
+            .mh(&[0x00, 0x00, 0x00, 0x01])
+            .build()
+            .unwrap();
+
+        let style = StyleFile::parse(&bytes).unwrap();
+        assert_eq!(style.tracks.len(), 1);
+        assert_eq!(style.records().collect::<Vec<_>>(), vec![&record]);
+        let ctabs = style.ctabs().collect::<Vec<_>>();
+        assert_eq!(ctabs.len(), 1);
+        assert_eq!(ctabs[0].name(), ctab.name());
+        assert!(ctabs[0].equivalent_for_playback(&ctab));
+        assert_eq!(style.ots.as_ref().unwrap().raw(), &ots[..]);
+        assert_eq!(style.mh.as_ref().unwrap().raw(), &[0x00, 0x00, 0x00, 0x01]);
+
+        // Sections without content are left out
+        let bytes = StyleFileBuilder::new().build().unwrap();
+        let style = StyleFile::parse(&bytes).unwrap();
+        assert!(style.casm.is_none() && style.mdb.is_none());
+        assert!(style.ots.is_none() && style.mh.is_none());
+
+        // Every table that cannot be encoded is reported
+        let autostart = |byte: u8| Some(Chord::SpecialAutostart).filter(|_| byte == 0x7F);
+        let options = ParseOptions {
+            chord_alias: Some(&autostart),
+            ..ParseOptions::default()
+        };
+        let mut raw = CTAB2_BASS.to_vec();
+        raw[19] = 0x7F;
+        let bad = Ctab::read_with(Chunk::Ctab2(&raw), &options).unwrap();
+        let errors = StyleFileBuilder::new()
+            .cseg(Cseg::new(vec![StylePart::MainA], vec![bad.clone()]))
+            .cseg(Cseg::new(vec![StylePart::MainB], vec![ctab, bad]))
+            .record(record)
+            .build()
+            .unwrap_err();
+        assert_eq!(errors, vec!["source chord type is not a chord"; 2]);
+    }
+
+    #[test]
+    fn record_truncated() {
+        let raw = [0x07, 0x27, 0x0E, 0x04, 0x04];
+        for len in 0..raw.len() {
+            assert!(Record::read(Chunk::Record(&raw[..len])).is_err());
+        }
+        assert!(Record::read(Chunk::Record(&raw)).is_ok());
+    }
+
+    #[test]
+    fn downgrade_to_v1() {
+        // Dorian is only available in SFFv2
+        let mut raw = CTAB2_BASS.to_vec();
+        for ntt in [23, 29, 35] {
+            raw[ntt] = 0x09;
+        }
+        let v2 = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        let (v1, lost) = v2.downgrade_to_v1();
+        assert_eq!(v1.version(), Version::Ctab1);
+        assert_eq!(v1.tables().len(), 1);
+        assert_eq!(v1.tables()[0].ntt(), TranspositionTable::Melody);
+        assert_eq!(v1.special(), None);
+        assert_eq!(
+            lost,
+            [
+                LostFeature::ZoneTables,
+                LostFeature::TranspositionTable(TranspositionTable::Dorian),
+                LostFeature::Special,
+            ]
+        );
+
+        // Melody with bass mode on is the SFFv1 bass table
+        let v2 = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        let (v1, lost) = v2.downgrade_to_v1();
+        assert_eq!(v1.tables()[0].ntt(), TranspositionTable::Bass);
+        assert!(!lost.contains(&LostFeature::BassMode));
+
+        // SFFv1 tables are left alone
+        let v1 = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        assert!(v1.downgrade_to_v1().1.is_empty());
+    }
+
+    #[test]
+    fn stray_chunk_in_cseg() {
+        let record = chunk(b"FNRP", &[0x07, 0xA1, 0x20, 0x04, 0x04]);
+        let cseg = [
+            chunk(b"Sdec", b"Main A"),
+            chunk(b"Ctab", CTAB1_DRUMS),
+            record,
+            chunk(b"Ctab", CTAB1_CHORD),
+        ]
+        .concat();
+        let mut raw = chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]);
+        raw.extend(chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]));
+        raw.extend(chunk(b"CASM", &chunk(b"CSEG", &cseg)));
+
+        let skipped = RefCell::new(Vec::new());
+        let on_skip = |err: &Error| skipped.borrow_mut().push(err.kind().message());
+        let options = ParseOptions {
+            on_skip: Some(&on_skip),
+            ..ParseOptions::default()
+        };
+        let style = StyleFile::parse_with(&raw, options);
+        if cfg!(feature = "strict") {
+            assert!(style.is_err());
+        } else {
+            let casm = style.unwrap().casm.unwrap();
+            let cseg = &casm.csegs()[0];
+            assert_eq!(cseg.style_parts(), &[StylePart::MainA]);
+            assert_eq!(cseg.ctabs().len(), 2);
+            assert_eq!(cseg.ctabs()[1].name(), "Pf L 1");
+            assert_eq!(
+                skipped.into_inner(),
+                vec!["found a chunk not belonging in a CASM section"]
+            );
+        }
+    }
+
+    #[test]
+    fn stray_chunk_between_items() {
+        let record = chunk(b"FNRP", &[0x07, 0xA1, 0x20, 0x04, 0x04]);
+        let cseg = chunk(b"CSEG", &chunk(b"Sdec", b"Main A"));
+        let mut raw = chunk(b"MThd", &[0x00, 0x00, 0x00, 0x01, 0x01, 0xE0]);
+        raw.extend(chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]));
+        raw.extend(chunk(b"CASM", &[&cseg[..], &record, &cseg].concat()));
+        raw.extend(chunk(b"FNRc", &[&record[..], &cseg, &record].concat()));
+
+        let skipped = RefCell::new(Vec::new());
+        let on_skip = |err: &Error| skipped.borrow_mut().push(err.kind().message());
+        let options = ParseOptions {
+            on_skip: Some(&on_skip),
+            ..ParseOptions::default()
+        };
+        let style = StyleFile::parse_with(&raw, options);
+        if cfg!(feature = "strict") {
+            assert!(style.is_err());
+        } else {
+            let style = style.unwrap();
+            assert_eq!(style.casm.unwrap().csegs().len(), 2);
+            assert_eq!(style.mdb.unwrap().records().len(), 2);
+            assert_eq!(
+                skipped.into_inner(),
+                vec![
+                    "found a chunk not belonging in a CASM section",
+                    "found a chunk not belonging in an MDB section"
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn iterators_stop_after_error() {
+        let good = chunk(b"CSEG", &chunk(b"Sdec", b"Main A"));
+        let bad = chunk(b"CSEG", &chunk(b"Ctab", &CTAB1_CHORD[..10]));
+        let raw = [&good[..], &bad, &good].concat();
+        let mut csegs = CsegIter::new(&raw);
+        assert!(csegs.next().unwrap().is_ok());
+        if cfg!(feature = "strict") {
+            assert!(csegs.next().unwrap().is_err());
+        } else {
+            // Only the bad one is skipped
+            assert!(csegs.next().unwrap().is_ok());
+        }
+        assert!(csegs.next().is_none());
+        assert!(csegs.next().is_none());
+
+        let good = chunk(b"FNRP", &[0x07, 0x27, 0x0E, 0x04, 0x04]);
+        let bad = chunk(b"FNRP", &[0x07, 0x27]);
+        let raw = [&good[..], &bad, &good].concat();
+        let mut records = RecordIter::new(&raw);
+        assert!(records.next().unwrap().is_ok());
+        if cfg!(feature = "strict") {
+            assert!(records.next().unwrap().is_err());
+        } else {
+            // Only the bad one is skipped
+            assert!(records.next().unwrap().is_ok());
+        }
+        assert!(records.next().is_none());
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn range_boundaries() {
+        let v1 = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        assert_eq!(v1.table_count(), 1);
+        assert_eq!(v1.range_boundaries(), None);
+        assert_eq!(v1.table_for_note(u7::new(0)), &v1.tables()[0]);
+        assert_eq!(v1.table_for_note(u7::new(127)), &v1.tables()[0]);
+
+        // Mid zone from 0x30 to 0x47
+        let mut raw = CTAB2_BASS.to_vec();
+        raw[20] = 0x30;
+        raw[21] = 0x47;
+        let v2 = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        assert_eq!(v2.table_count(), 3);
+        assert_eq!(v2.range_boundaries(), Some((u7::new(0x30), u7::new(0x47))));
+        let zone = |note: u8| {
+            let table = v2.table_for_note(u7::new(note));
+            v2.tables().iter().position(|t| core::ptr::eq(t, table))
+        };
+        assert_eq!(zone(0x2F), Some(0));
+        assert_eq!(zone(0x30), Some(1));
+        assert_eq!(zone(0x47), Some(1));
+        assert_eq!(zone(0x48), Some(2));
+    }
+
+    #[test]
+    fn set_dest_channel() {
+        let mut ctab = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        assert_eq!(ctab.dest_role(), Some(AccompanimentPart::Bass));
+        ctab.set_dest_channel(12).unwrap();
+        assert_eq!(ctab.dest_channel_number(), 12);
+        assert_eq!(ctab.dest().as_int(), 11);
+        assert_eq!(ctab.dest_role(), Some(AccompanimentPart::Chord1));
+        let mut out = Vec::new();
+        ctab.write(&mut out).unwrap();
+        assert_eq!(out[8 + 9], 0x0B);
+
+        for &channel in &[0, 1, 8, 17] {
+            let err = ctab.set_dest_channel(channel).unwrap_err();
+            assert_eq!(err.kind().message(), "not an accompaniment channel");
+        }
+        assert_eq!(ctab.dest_channel_number(), 12);
+    }
+
+    #[test]
+    fn identity_table() {
+        let bypass = Table::default();
+        assert!(bypass.is_identity());
+        for &root in Key::ALL.iter() {
+            let shift = bypass.resolve_root(root);
+            for note in (12..116).map(u7::new) {
+                let shifted = (note.as_int() as i8 + shift) as u8;
+                assert_eq!(bypass.transpose(note, root), u7::new(shifted));
+            }
+        }
+
+        let mut spec = CtabSpec::v2(0x0A, 0x0A, "E.Bass");
+        spec.tables[0].ntt = 0x09;
+        spec.tables[2].note_range = (0x18, 0x60);
+        let raw = spec.bytes();
+        let ctab = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        assert_eq!(ctab.tables()[0].ntt(), TranspositionTable::Dorian);
+        assert!(!ctab.tables()[0].is_identity());
+        assert!(ctab.tables()[1].is_identity());
+        assert!(!ctab.tables()[2].is_identity());
+        // The drum table keeps the notes close to the source
+        let drums = Ctab::read(Chunk::Ctab1(CTAB1_DRUMS)).unwrap();
+        assert_eq!(drums.tables()[0].ntr(), TranspositionType::RootFixed);
+        assert!(!drums.tables()[0].is_identity());
+    }
+
+    #[test]
+    fn on_chord_change() {
+        let mut spec = CtabSpec::v2(0x0A, 0x0A, "E.Bass");
+        spec.range = (0x30, 0x47);
+        spec.muted_notes = vec![Key::D];
+        spec.tables[0].retrigger_rule = RetriggerRule::RetriggerToRoot;
+        spec.tables[2].retrigger_rule = RetriggerRule::Stop;
+        let raw = spec.bytes();
+        let ctab = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        assert_eq!(ctab.tables()[1].retrigger_rule(), RetriggerRule::PitchShift);
+
+        let playing = [0x3C, 0x40, 0x50, 0x26].map(u7::new);
+        let actions = ctab.on_chord_change(&playing, Key::E, Chord::Maj);
+        assert_eq!(
+            actions,
+            vec![
+                NoteAction::Shift(u7::new(0x40)),
+                NoteAction::Shift(u7::new(0x44)),
+                NoteAction::Stop,
+                // F#2 is moved down to E2
+                NoteAction::Retrigger(u7::new(0x28)),
+            ]
+        );
+        // Every note stops on a muted chord
+        let actions = ctab.on_chord_change(&playing, Key::D, Chord::Maj);
+        assert!(actions.iter().all(|&action| action == NoteAction::Stop));
+        assert!(ctab.on_chord_change(&[], Key::E, Chord::Maj).is_empty());
+
+        // Recorded in D, the same notes a tone lower
+        spec.source_chord = Key::D;
+        let raw = spec.bytes();
+        let ctab = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        let playing = [0x3E, 0x42, 0x28].map(u7::new);
+        assert_eq!(
+            ctab.on_chord_change(&playing, Key::E, Chord::Maj),
+            vec![
+                NoteAction::Shift(u7::new(0x40)),
+                NoteAction::Shift(u7::new(0x44)),
+                NoteAction::Retrigger(u7::new(0x28)),
+            ]
+        );
+
+        assert_eq!(RetriggerRule::Stop.describe(), "Notes are stopped");
+    }
+
+    #[test]
+    fn harmonize() {
+        // The low zone follows the root up to B, the mid zone stays close to the source
+        let mut spec = CtabSpec::v2(0x0A, 0x0A, "E.Bass");
+        spec.range = (0x30, 0x47);
+        spec.muted_notes = vec![Key::D];
+        spec.tables[0].high_key = Key::B;
+        spec.tables[1].ntr = TranspositionType::RootFixed;
+        let raw = spec.bytes();
+        let mut ctab = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        let melody = [0x24, 0x28, 0x3C, 0x40].map(u7::new);
+
+        let notes = ctab.harmonize(&melody, Key::G, Chord::Maj);
+        let expected = [0x2B, 0x2F, 0x37, 0x3B].map(u7::new);
+        assert_eq!(notes, expected);
+        assert_eq!(ctab.harmonize(&melody, Key::C, Chord::Min7), melody);
+        assert!(ctab.harmonize(&[], Key::G, Chord::Maj).is_empty());
+        // Muted root and chord
+        assert!(ctab.harmonize(&melody, Key::D, Chord::Maj).is_empty());
+        ctab.mute_all_chords();
+        assert!(ctab.harmonize(&melody, Key::G, Chord::Maj).is_empty());
+
+        // A source recorded a tone higher plays the same notes
+        spec.muted_notes = Vec::new();
+        let raw = spec.bytes();
+        let mut ctab = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        let expected = [0x28, 0x2C, 0x40, 0x44].map(u7::new);
+        assert_eq!(ctab.harmonize(&melody, Key::E, Chord::Maj), expected);
+        ctab.rekey(Key::D);
+        let melody = [0x26, 0x2A, 0x3E, 0x42].map(u7::new);
+        assert_eq!(ctab.harmonize(&melody, Key::E, Chord::Maj), expected);
+    }
+
+    #[test]
+    fn zones() {
+        let notes = |zones: Vec<(u7, u7)>| {
+            zones
+                .into_iter()
+                .map(|(low, high)| (low.as_int(), high.as_int()))
+                .collect::<Vec<_>>()
+        };
+        let v1 = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        assert_eq!(notes(v1.zones()), vec![(0, 127)]);
+
+        let mut spec = CtabSpec::v2(0x0A, 0x0A, "E.Bass");
+        spec.range = (0x30, 0x47);
+        let raw = spec.bytes();
+        let v2 = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        assert_eq!(
+            notes(v2.zones()),
+            vec![(0, 0x2F), (0x30, 0x47), (0x48, 127)]
+        );
+        for (zone, table) in v2.zones().into_iter().zip(v2.tables()) {
+            assert_eq!(v2.table_for_note(zone.0), table);
+            assert_eq!(v2.table_for_note(zone.1), table);
+        }
+
+        // Empty low and high zones are left out
+        spec.range = (0x00, 0x47);
+        let raw = spec.bytes();
+        let v2 = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+        assert_eq!(notes(v2.zones()), vec![(0, 0x47), (0x48, 127)]);
+        let v2 = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        assert_eq!(notes(v2.zones()), vec![(0, 127)]);
+    }
+
+    #[test]
+    fn edit_tables() {
+        let mut v2 = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        assert!(v2.table_mut(RangeSlot::Full).is_none());
+        let mid = v2.table_mut(RangeSlot::Mid).unwrap();
+        assert_eq!(mid.retrigger_rule(), RetriggerRule::PitchShiftToRoot);
+        mid.set_retrigger_rule(RetriggerRule::PitchShift);
+        v2.set_range(u7::new(0x30), u7::new(0x47)).unwrap();
+        assert!(v2.set_range(u7::new(0x47), u7::new(0x30)).is_err());
+
+        let mut out = Vec::new();
+        v2.write(&mut out).unwrap();
+        let mut expected = CTAB2_BASS.to_vec();
+        expected[20..22].copy_from_slice(&[0x30, 0x47]);
+        expected[33] = RetriggerRule::PitchShift as u8;
+        assert_eq!(out[8..], expected[..]);
+
+        let mut v1 = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        assert!(v1.table_mut(RangeSlot::Mid).is_none());
+        assert!(v1.table_mut(RangeSlot::Full).is_some());
+        let err = v1.set_range(u7::new(0), u7::new(127)).unwrap_err();
+        assert_eq!(err.kind().message(), "SFFv1 tables have no mid zone");
+    }
+
+    #[test]
+    fn mute_all() {
+        let mut ctab = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        ctab.unmute_all_chords();
+        assert!(Chord::ALL
+            .iter()
+            .filter(|chord| chord.is_chord_type())
+            .all(|&chord| !ctab.is_chord_muted(chord)));
+        assert_eq!(ctab.encode_chord_mute(), [0x03, 0xFF, 0xFF, 0xFF, 0xFF]);
+        ctab.mute_all_chords();
+        assert!(Chord::ALL
+            .iter()
+            .filter(|chord| chord.is_chord_type())
+            .all(|&chord| ctab.is_chord_muted(chord)));
+        assert_eq!(ctab.encode_chord_mute(), [0x00; 5]);
+
+        // The drums autostart flag is kept
+        let mut ctab = Ctab::read(Chunk::Ctab1(CTAB1_DRUMS)).unwrap();
+        assert!(ctab.drums_autostart());
+        ctab.mute_all_chords();
+        assert!(ctab.drums_autostart());
+        assert_eq!(ctab.encode_chord_mute(), [0x04, 0x00, 0x00, 0x00, 0x00]);
+
+        ctab.mute_all_notes();
+        assert!(Key::ALL.iter().all(|&key| ctab.is_note_muted(key)));
+        assert_eq!(ctab.encode_note_mute(), [0x00, 0x00]);
+        ctab.unmute_all_notes();
+        assert!(Key::ALL.iter().all(|&key| !ctab.is_note_muted(key)));
+        assert_eq!(ctab.encode_note_mute(), [0x0F, 0xFF]);
+    }
+
+    #[test]
+    fn played_chord_intervals() {
+        // Only major and minor are played
+        let mut raw = CTAB1_CHORD.to_vec();
+        raw[13..18].copy_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x01]);
+        let ctab = Ctab::read(Chunk::Ctab1(&raw)).unwrap();
+        assert_eq!(
+            ctab.played_chord_intervals(),
+            [&[0, 4, 7][..], &[0, 3, 7][..]]
+        );
+
+        let mut ctab = Ctab::read(Chunk::Ctab1(CTAB1_CHORD)).unwrap();
+        ctab.mute_all_chords();
+        assert!(ctab.played_chord_intervals().is_empty());
+    }
+
+    #[test]
+    fn response_matrix() {
+        // C# and D are muted
+        let mut raw = CTAB1_CHORD.to_vec();
+        raw[12] = 0b1111_1001;
+        let ctab = Ctab::read(Chunk::Ctab1(&raw)).unwrap();
+        let matrix = ctab.response_matrix();
+        assert_eq!(matrix.len(), 12 * 34);
+        assert_eq!(matrix[0], (Key::C, Chord::Maj, true));
+        let cell = |root, chord| {
+            matrix
+                .iter()
+                .find(|cell| cell.0 == root && cell.1 == chord)
+                .unwrap()
+                .2
+        };
+        // Muted root note
+        assert!(!cell(Key::D, Chord::Maj));
+        // Muted chord type
+        assert!(ctab.is_chord_muted(Chord::OnePlusEight));
+        assert!(!cell(Key::C, Chord::OnePlusEight));
+        // Both
+        assert!(!cell(Key::Cs, Chord::OnePlusEight));
+        // Neither
+        assert!(cell(Key::E, Chord::Min7));
+    }
+
+    #[test]
+    fn plays() {
+        // C# and D are muted
+        let mut raw = CTAB1_CHORD.to_vec();
+        raw[12] = 0b1111_1001;
+        let ctab = Ctab::read(Chunk::Ctab1(&raw)).unwrap();
+        // Muted root
+        assert!(!ctab.plays(Key::D, Chord::Maj));
+        // Muted chord
+        assert!(!ctab.plays(Key::C, Chord::OnePlusEight));
+        // Fully allowed
+        assert!(ctab.plays(Key::C, Chord::Maj));
+        assert!(ctab.plays(Key::E, Chord::Min7));
+
+        // The chord cancel ignores the root
+        assert!(ctab.plays(Key::D, Chord::Cancel));
+        // Special flags are not played chords
+        let drums = Ctab::read(Chunk::Ctab1(CTAB1_DRUMS)).unwrap();
+        assert!(!drums.is_chord_muted(Chord::SpecialAutostart));
+        assert!(!drums.plays(Key::C, Chord::SpecialAutostart));
+    }
+
+    #[test]
+    fn sub_chunks() {
+        // Collect the leaves of the chunk tree, with their depth
+        fn leaves<'a>(chunk: Chunk<'a>, depth: usize, out: &mut Vec<(usize, Chunk<'a>)>) {
+            match chunk.sub_chunks() {
+                Some(sub_chunks) => {
+                    for sub_chunk in sub_chunks {
+                        leaves(sub_chunk.unwrap(), depth + 1, out);
+                    }
+                }
+                None => out.push((depth, chunk)),
+            }
+        }
+        let cseg = [
+            chunk(b"Sdec", b"Main A"),
+            chunk(b"Ctab", CTAB1_DRUMS),
+            chunk(b"Ctab", CTAB1_CHORD),
+        ]
+        .concat();
+        let casm = chunk(b"CSEG", &cseg);
+        let mut out = Vec::new();
+        leaves(Chunk::Casm(&casm), 0, &mut out);
+        assert_eq!(out.len(), 3);
+        assert!(matches!(out[0], (2, Chunk::Sdec(b"Main A"))));
+        assert!(matches!(out[1], (2, Chunk::Ctab1(ctab)) if ctab == CTAB1_DRUMS));
+        assert!(matches!(out[2], (2, Chunk::Ctab1(ctab)) if ctab == CTAB1_CHORD));
+
+        assert!(Chunk::Ctab1(CTAB1_CHORD).sub_chunks().is_none());
+        // The record header is skipped
+        let record = [
+            &[0x07, 0x27, 0x0E, 0x04, 0x04][..],
+            &chunk(b"Mnam", b"Title"),
+        ]
+        .concat();
+        let mut sub_chunks = Chunk::Record(&record).sub_chunks().unwrap();
+        assert!(matches!(
+            sub_chunks.next(),
+            Some(Ok(Chunk::SongTitleData(b"Title")))
+        ));
+        assert!(sub_chunks.next().is_none());
+    }
+
+    #[test]
+    fn meter_class() {
+        let signature = |upper: u8, lower: u8| {
+            Record::read(Chunk::Record(&[0x07, 0x27, 0x0E, upper, lower]))
+                .unwrap()
+                .signature()
+        };
+        for &(upper, lower, class) in &[
+            (2, 4, MeterClass::Simple),
+            (3, 4, MeterClass::Simple),
+            (4, 4, MeterClass::Simple),
+            (2, 2, MeterClass::Simple),
+            (8, 4, MeterClass::Simple),
+            (6, 8, MeterClass::Compound),
+            (9, 8, MeterClass::Compound),
+            (12, 8, MeterClass::Compound),
+            (6, 4, MeterClass::Compound),
+            (5, 4, MeterClass::Complex),
+            (7, 8, MeterClass::Complex),
+            (11, 8, MeterClass::Complex),
+        ] {
+            assert_eq!(
+                signature(upper, lower).meter_class(),
+                class,
+                "{}/{}",
+                upper,
+                lower
+            );
+        }
+    }
+
+    #[test]
+    fn keyword_frequencies() {
+        let record = |kwd1: &[u8], kwd2: Option<&[u8]>| {
+            let mut data = [&[0x07, 0xA1, 0x20, 0x04, 0x04][..], &chunk(b"Kwd1", kwd1)].concat();
+            if let Some(kwd2) = kwd2 {
+                data.extend(chunk(b"Kwd2", kwd2));
+            }
+            chunk(b"FNRP", &data)
+        };
+        let raw = [
+            record(b"Jazz", Some(b"Swing")),
+            record(b" jazz ", None),
+            record(b"Ballad", Some(b"JAZZ")),
+            record(b"", Some(b"swing")),
+        ]
+        .concat();
+        let mdb = Mdb::parse(
+            ChunkIter::new(&chunk(b"FNRc", &raw)),
+            &ParseOptions::default(),
+        )
+        .unwrap()
+        .unwrap();
+        let frequencies = mdb.keyword_frequencies();
+        assert_eq!(frequencies.len(), 3);
+        assert_eq!(frequencies["jazz"], 3);
+        assert_eq!(frequencies["swing"], 2);
+        assert_eq!(frequencies["ballad"], 1);
+    }
+
+    #[test]
+    fn common_signatures() {
+        assert_eq!(Signature::SIX_EIGHT.numerator(), 6);
+        assert_eq!(Signature::SIX_EIGHT.denominator(), 8);
+        assert_eq!(Signature::SIX_EIGHT.meter_class(), MeterClass::Compound);
+        // Stored as in records, with the plain denominator
+        let record = Record::read(Chunk::Record(&[0x07, 0x27, 0x0E, 0x04, 0x04])).unwrap();
+        assert_eq!(record.signature(), Signature::FOUR_FOUR);
+        assert_eq!(Signature::common().len(), 9);
+        assert!(Signature::common().contains(&Signature::THREE_FOUR));
+    }
+
+    #[test]
+    fn signature_tuples() {
+        assert_eq!(Signature::from((6, 8)), Signature::SIX_EIGHT);
+        assert_eq!(<(u8, u8)>::from(Signature::THREE_FOUR), (3, 4));
+        for &signature in Signature::common() {
+            let pair: (u8, u8) = signature.into();
+            assert_eq!(Signature::from(pair), signature);
+        }
+        // Unusual pairs are kept as is
+        let odd = Signature::from((0, 3));
+        assert_eq!((odd.numerator(), odd.denominator()), (0, 3));
+        assert_eq!(<(u8, u8)>::from(odd), (0, 3));
+    }
+
+    #[test]
+    fn ctab_layout() {
+        use crate::ctab::layout::{self, CtabLayout};
+        // The common fields are contiguous and fill the common part
+        let common = [
+            layout::SOURCE..layout::SOURCE + 1,
+            layout::NAME,
+            layout::DEST..layout::DEST + 1,
+            layout::EDITABLE..layout::EDITABLE + 1,
+            layout::NOTE_MUTE,
+            layout::CHORD_MUTE,
+            layout::SOURCE_CHORD..layout::SOURCE_CHORD + 1,
+            layout::SOURCE_CHORD_TYPE..layout::SOURCE_CHORD_TYPE + 1,
+        ];
+        let mut end = 0;
+        for field in common.iter() {
+            assert_eq!(field.start, end);
+            end = field.end;
+        }
+        assert_eq!(end, layout::COMMON_SIZE);
+        assert_eq!(
+            common.iter().map(|field| field.len()).sum::<usize>(),
+            layout::COMMON_SIZE
+        );
+
+        // So are the version specific ones
+        let v1 = &CtabLayout::V1;
+        assert_eq!(v1.tables.start, layout::COMMON_SIZE);
+        assert_eq!(v1.special.start, v1.tables.end);
+        assert_eq!(v1.table_count(), 1);
+        assert_eq!(
+            v1.size(),
+            CTAB1_CHORD.len() + layout::CTAB1_SPECIAL_SIZE - 1
+        );
+        let v2 = &CtabLayout::V2;
+        let range = v2.range.clone().unwrap();
+        assert_eq!(range.start, layout::COMMON_SIZE);
+        assert_eq!(v2.tables.start, range.end);
+        assert_eq!(v2.special.start, v2.tables.end);
+        assert_eq!(v2.table_count(), 3);
+        assert_eq!(
+            range.len() + v2.tables.len() + v2.special.len(),
+            v2.size() - layout::COMMON_SIZE
+        );
+        assert_eq!(v2.size(), CTAB2_BASS.len());
+    }
+
+    #[test]
+    fn ctab_table_count() {
+        // Three tables followed by the special bytes
+        let ctab = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        assert_eq!(ctab.table_count(), 3);
+        assert_eq!(ctab.special(), Some(&CTAB2_BASS[40..]));
+
+        // A fourth table
+        let raw = [&CTAB2_BASS[..40], &CTAB2_BASS[34..40], &CTAB2_BASS[40..]].concat();
+        if cfg!(feature = "strict") {
+            let err = Ctab::read(Chunk::Ctab2(&raw)).unwrap_err();
+            assert_eq!(
+                err.kind().message(),
+                "unexpected number of transposition tables"
+            );
+        } else {
+            let ctab = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+            assert_eq!(ctab.table_count(), 4);
+            assert_eq!(ctab.tables()[3], ctab.tables()[2]);
+            assert_eq!(ctab.special(), Some(&CTAB2_BASS[40..]));
+            // The extra table is written back
+            let mut out = Vec::new();
+            ctab.write(&mut out).unwrap();
+            assert_eq!(out, chunk(b"Ctb2", &raw));
+        }
+
+        // A single table followed by the special bytes
+        let raw = [&CTAB2_BASS[..28], &CTAB2_BASS[40..]].concat();
+        if cfg!(feature = "strict") {
+            assert!(Ctab::read(Chunk::Ctab2(&raw)).is_err());
+        } else {
+            let ctab = Ctab::read(Chunk::Ctab2(&raw)).unwrap();
+            let full = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+            assert_eq!(ctab.tables()[0], full.tables()[0]);
+            assert_eq!(ctab.tables()[1..], [Table::default(), Table::default()]);
+            assert!(ctab.trailer_present());
+            assert_eq!(ctab.special(), Some(&CTAB2_BASS[40..]));
+        }
+
+        // A damaged trailer is not read as a fourth table
+        let short = &CTAB2_BASS[..CTAB2_BASS.len() - 1];
+        let long = [CTAB2_BASS, &[0x00]].concat();
+        if cfg!(feature = "strict") {
+            let err = Ctab::read(Chunk::Ctab2(short)).unwrap_err();
+            assert_eq!(
+                err.kind().message(),
+                "missing special bytes at the end of CTABv2"
+            );
+        } else {
+            let ctab = Ctab::read(Chunk::Ctab2(short)).unwrap();
+            assert_eq!(ctab.table_count(), 3);
+            assert!(!ctab.trailer_present());
+        }
+        let ctab = Ctab::read(Chunk::Ctab2(&long)).unwrap();
+        assert_eq!(ctab.table_count(), 3);
+        assert_eq!(ctab.special(), Some(&CTAB2_BASS[40..]));
+    }
+
+    #[test]
+    fn ctab_write() {
+        for (chunk, raw) in [
+            (Chunk::Ctab1(CTAB1_DRUMS), CTAB1_DRUMS),
+            (Chunk::Ctab1(CTAB1_CHORD), CTAB1_CHORD),
+            (Chunk::Ctab2(CTAB2_BASS), CTAB2_BASS),
+        ] {
+            let ctab = Ctab::read(chunk).unwrap();
+            assert_eq!(ctab.encode().unwrap(), raw);
+        }
+        let ctab = Ctab::read(Chunk::Ctab2(CTAB2_BASS)).unwrap();
+        let mut out = Vec::new();
+        ctab.write(&mut out).unwrap();
+        assert_eq!(&out[..8], b"Ctb2\0\0\0\x2F");
+        assert_eq!(&out[8..], CTAB2_BASS);
+
+        // Every table of the test files is written back as is
+        fn check(chunk: Chunk) {
+            match chunk {
+                Chunk::Ctab1(raw) | Chunk::Ctab2(raw) => {
+                    assert_eq!(Ctab::read(chunk).unwrap().encode().unwrap(), raw)
+                }
+                _ => {
+                    for sub_chunk in chunk.sub_chunks().into_iter().flatten() {
+                        check(sub_chunk.unwrap());
+                    }
+                }
+            }
+        }
+        for raw in [
+            &include_bytes!("../test-asset/sff1.prs")[..],
+            &include_bytes!("../test-asset/sff2.prs")[..],
+        ] {
+            for chunk in ChunkIter::new(raw) {
+                check(chunk.unwrap());
+            }
+        }
+    }
+}